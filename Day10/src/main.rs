@@ -3,7 +3,38 @@ use std::collections::HashMap;
 
 type Position = (usize, usize);
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Direction {
+  North,
+  East,
+  South,
+  West
+}
+
+impl Direction {
+  const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+  fn opposite(self) -> Direction {
+    match self {
+      Direction::North => Direction::South,
+      Direction::East => Direction::West,
+      Direction::South => Direction::North,
+      Direction::West => Direction::East
+    }
+  }
+
+  // The neighbouring position in this direction, if it is still on the map.
+  fn step(self, position: Position, map: &[Vec<Tile>]) -> Option<Position> {
+    match self {
+      Direction::North => (position.0 > 0).then(|| (position.0 - 1, position.1)),
+      Direction::South => (position.0 + 1 < map.len()).then_some((position.0 + 1, position.1)),
+      Direction::West => (position.1 > 0).then(|| (position.0, position.1 - 1)),
+      Direction::East => (position.1 + 1 < map[position.0].len()).then_some((position.0, position.1 + 1))
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Tile {
   Ground,
   Vertical,
@@ -15,7 +46,49 @@ enum Tile {
   Starting
 }
 
-fn parse_input(lines: &Vec<&str>) -> (Position, Vec<Vec<Tile>>) {
+impl Tile {
+  const PIPES: [Tile; 6] = [Tile::Vertical, Tile::Horizontal, Tile::NtoE, Tile::NtoW, Tile::StoW, Tile::StoE];
+
+  fn connections(&self) -> &'static [Direction] {
+    match self {
+      Tile::Vertical => &[Direction::North, Direction::South],
+      Tile::Horizontal => &[Direction::East, Direction::West],
+      Tile::NtoE => &[Direction::North, Direction::East],
+      Tile::NtoW => &[Direction::North, Direction::West],
+      Tile::StoW => &[Direction::South, Direction::West],
+      Tile::StoE => &[Direction::South, Direction::East],
+      Tile::Ground | Tile::Starting => &[]
+    }
+  }
+
+  // Light box-drawing characters for ordinary pipes, heavy ones for the main loop.
+  fn box_char(&self, heavy: bool) -> char {
+    match (self, heavy) {
+      (Tile::Vertical, false) => '│',
+      (Tile::Horizontal, false) => '─',
+      (Tile::NtoE, false) => '└',
+      (Tile::NtoW, false) => '┘',
+      (Tile::StoW, false) => '┐',
+      (Tile::StoE, false) => '┌',
+      (Tile::Vertical, true) => '┃',
+      (Tile::Horizontal, true) => '━',
+      (Tile::NtoE, true) => '┗',
+      (Tile::NtoW, true) => '┛',
+      (Tile::StoW, true) => '┓',
+      (Tile::StoE, true) => '┏',
+      (Tile::Ground, _) => '░',
+      (Tile::Starting, _) => 'S'
+    }
+  }
+
+  fn from_connections(first: Direction, second: Direction) -> Option<Tile> {
+    Tile::PIPES.into_iter()
+      .find(|tile| first != second && tile.connections().contains(&first) && tile.connections().contains(&second))
+  }
+}
+
+// The grid as it is, with S kept as Tile::Starting and its position if there is one
+fn parse_map(lines: &[&str]) -> Result<(Option<Position>, Vec<Vec<Tile>>), String> {
   let mut starting_position: Option<Position> = None;
   let mut map: Vec<Vec<Tile>> = Vec::new();
   for (i, line) in lines.iter().enumerate() {
@@ -34,187 +107,367 @@ fn parse_input(lines: &Vec<&str>) -> (Position, Vec<Vec<Tile>>) {
           starting_position = Some((i, j));
           file.push(Tile::Starting)
         },
-        _ => panic!("Found invalid tile {}!", tile)
+        _ => return Err(format!("Line {}: Found invalid tile {}!", i + 1, tile))
       }
     }
     map.push(file);
   }
-  match starting_position {
-    Some(p) => return (p, map),
-    None => panic!("There is no starting position!")
-  }  
+
+  Ok((starting_position, map))
 }
 
-fn find_starting_positions(starting_position: Position, map: &Vec<Vec<Tile>>) -> (Position, Position) {
-  let mut found: Vec<Position> = Vec::new();
-  let mut positions_to_look: Vec<(Position, Vec<Tile>)> = Vec::new();
-  if starting_position.0 > 0 {
-    positions_to_look.push(((starting_position.0 - 1, starting_position.1), vec![Tile::Vertical, Tile::StoE, Tile::StoW]));
-  }
-  if starting_position.1 > 0 {
-    positions_to_look.push(((starting_position.0, starting_position.1 - 1), vec![Tile::Horizontal, Tile::StoE, Tile::NtoE]));
-  }
-  if starting_position.0 < map.len() - 1 {
-    positions_to_look.push(((starting_position.0 + 1, starting_position.1), vec![Tile::Vertical, Tile::NtoE, Tile::NtoW]));
-  }
-  if starting_position.0 < map[0].len() - 1 {
-    positions_to_look.push(((starting_position.0, starting_position.1 + 1), vec![Tile::Horizontal, Tile::StoW, Tile::NtoW]));
-  }
-  for position in positions_to_look {
-    if position.1.contains(&map[position.0.0][position.0.1]) {
-      found.push(position.0);
-    }
+fn parse_input(lines: &[&str]) -> Result<(Position, Vec<Vec<Tile>>), String> {
+  match parse_map(lines)? {
+    (Some(p), map) => Ok((p, map)),
+    (None, _) => Err("There is no starting position!".to_owned())
   }
-
-  (found[0], found[1])
 }
 
-fn find_next_position(current_position: Position, previous_position: Position, map: &Vec<Vec<Tile>>) -> Position {
-  match map[current_position.0][current_position.1] {
-    Tile::Vertical => {
-      if current_position.0 > 0 && (current_position.0 - 1, current_position.1) != previous_position {
-        return (current_position.0 - 1, current_position.1);
-      } else {
-        return (current_position.0 + 1, current_position.1);
-      }
-    },
-    Tile::Horizontal => {
-      if current_position.1 > 0 && (current_position.0, current_position.1 - 1) != previous_position {
-        return (current_position.0, current_position.1 - 1);
-      } else {
-        return (current_position.0, current_position.1 + 1);
-      }
-    },
-    Tile::NtoE => {
-      if current_position.0 > 0 && (current_position.0 - 1, current_position.1) != previous_position {
-        return (current_position.0 - 1, current_position.1);
-      } else {
-        return (current_position.0, current_position.1 + 1);
-      }
-    },
-    Tile::NtoW => {
-      if current_position.0 > 0 && (current_position.0 - 1, current_position.1) != previous_position {
-        return (current_position.0 - 1, current_position.1);
-      } else {
-        return (current_position.0, current_position.1 - 1);
-      }
-    },
-    Tile::StoW => {
-      if current_position.0 < map.len() - 1 && (current_position.0 + 1, current_position.1) != previous_position {
-        return (current_position.0 + 1, current_position.1);
-      } else {
-        return (current_position.0, current_position.1 - 1);
-      }
-    },
-    Tile::StoE => {
-      if current_position.0 < map.len() - 1 && (current_position.0 + 1, current_position.1) != previous_position {
-        return (current_position.0 + 1, current_position.1);
-      } else {
-        return (current_position.0, current_position.1 + 1);
-      }
-    },
-    _ => {
-      panic!("Cannot find next position from {:?}", current_position);
-    }
+// S hides the pipe underneath it; the only shape that fits is the one joining the two
+// neighbours whose pipes point back at S.
+fn infer_starting_tile(starting_position: Position, map: &[Vec<Tile>]) -> Result<Tile, String> {
+  let connected: Vec<Direction> = Direction::ALL.into_iter()
+    .filter(|direction| match direction.step(starting_position, map) {
+      Some((i, j)) => map[i][j].connections().contains(&direction.opposite()),
+      None => false
+    })
+    .collect();
+  match connected[..] {
+    [first, second] => Tile::from_connections(first, second)
+      .ok_or(format!("No pipe joins {:?} and {:?}", first, second)),
+    [] => Err(format!("No pipe connects to the starting position {:?}", starting_position)),
+    [only] => Err(format!("Only the pipe to the {:?} connects to the starting position {:?}", only, starting_position)),
+    _ => Err(format!("{} pipes connect to the starting position {:?} ({:?}), cannot tell which two form the loop",
+      connected.len(), starting_position, connected))
   }
 }
 
-fn print_distance_map(distances: &HashMap<Position, usize>, map: &Vec<Vec<Tile>>) {
-  let mut i = 0;
-  while i < map.len() {
-    let mut j = 0;
-    let mut line: Vec<String> = Vec::new();
-    while j < map[i].len() {
-      if map[i][j] == Tile::Starting {
-        line.push("  0".to_owned());
-      } else if distances.contains_key(&(i, j)) {
-        line.push(format!("{: >3}", distances.get(&(i, j)).unwrap().to_string()));
-      } else {
-        line.push("  .".to_owned());
-      }
-      j = j + 1;
-    }
-    println!("{:?}", line.join(""));
-    i = i + 1;
-  }
+// The two neighbours the (already inferred) starting tile connects to.
+fn find_starting_positions(starting_position: Position, map: &[Vec<Tile>]) -> (Position, Position) {
+  let found: Vec<Position> = map[starting_position.0][starting_position.1].connections().iter()
+    .filter_map(|direction| direction.step(starting_position, map))
+    .collect();
+
+  (found[0], found[1])
+}
+
+fn find_next_position(current_position: Position, previous_position: Position, map: &[Vec<Tile>]) -> Position {
+  map[current_position.0][current_position.1].connections().iter()
+    .filter_map(|direction| direction.step(current_position, map))
+    .find(|position| *position != previous_position)
+    .unwrap_or_else(|| panic!("Cannot find next position from {:?}", current_position))
 }
 
-fn prob_a(starting_position: Position, map: &Vec<Vec<Tile>>) -> usize {
+fn prob_a(starting_position: Position, map: &[Vec<Tile>]) -> usize {
   let mut current_distance: usize = 1;
   let mut distances: HashMap<Position, usize> = HashMap::new();
-  let mut current_positions: (Position, Position) = find_starting_positions(starting_position, &map);
+  let mut current_positions: (Position, Position) = find_starting_positions(starting_position, map);
   let mut previous_positions: (Position, Position) = (starting_position, starting_position);
   while !distances.contains_key(&current_positions.0) && !distances.contains_key(&current_positions.1) {
     distances.insert(current_positions.0, current_distance);
     distances.insert(current_positions.1, current_distance);
     let new_previous_positions = current_positions;
     current_positions = (
-      find_next_position(current_positions.0, previous_positions.0, &map),
-      find_next_position(current_positions.1, previous_positions.1, &map)
+      find_next_position(current_positions.0, previous_positions.0, map),
+      find_next_position(current_positions.1, previous_positions.1, map)
     );
     previous_positions = new_previous_positions;
-    current_distance = current_distance + 1;
+    current_distance += 1;
   }
   current_distance - 1
 }
 
-fn prob_b(starting_position: Position, map: &Vec<Vec<Tile>>) -> usize {
-  let mut current_distance: usize = 1;
-  let mut distances: HashMap<Position, usize> = HashMap::new();
-  let mut current_position: Position = find_starting_positions(starting_position, &map).0;
+// Walks the loop from S back to S, so consecutive entries are neighbouring tiles and the
+// last tile connects back to the first.
+fn trace_loop(starting_position: Position, map: &[Vec<Tile>]) -> Vec<Position> {
+  let mut vertices: Vec<Position> = vec![starting_position];
+  let mut current_position: Position = find_starting_positions(starting_position, map).0;
   let mut previous_position: Position = starting_position;
-  while map[current_position.0][current_position.1] != Tile::Starting {
-    distances.insert(current_position, current_distance);
+  while current_position != starting_position {
+    vertices.push(current_position);
     let new_previous_position = current_position;
-    current_position = find_next_position(current_position, previous_position, &map);
+    current_position = find_next_position(current_position, previous_position, map);
     previous_position = new_previous_position;
-    current_distance = current_distance + 1;
   }
+  vertices
+}
 
-  print_distance_map(&distances, &map);
+// Steps from S to every loop tile, going whichever way round is shorter.
+fn loop_distances(vertices: &[Position]) -> HashMap<Position, usize> {
+  vertices.iter()
+    .enumerate()
+    .map(|(i, position)| (*position, i.min(vertices.len() - i)))
+    .collect()
+}
 
-  // Find inside and outside tiles
-  let mut enclosed_tiles = 0;
-  let mut i = 1;
-  while i < map.len() - 1 {
-    let mut j = 0;
-    let mut inside: i32 = 0;
-    let mut to_print: Vec<char> = Vec::new();
-    while j < map[i].len() {
-      //println!("Loop at {}, {}", i, j);
-      let distance_1 = if map[i][j] == Tile::Starting { Some(&0) } else { distances.get(&(i, j)) };
-      if distance_1 == None {
-        if inside != 0 {
-          //println!("Found enclosed tile at {}, {}; Inside value {}", i, j, inside);
-          enclosed_tiles = enclosed_tiles + 1;
+// The shoelace formula gives the area of the polygon through the tile centres, and Pick's
+// theorem (A = I + B/2 - 1) turns it into the number of tiles strictly inside the loop.
+fn prob_b(starting_position: Position, map: &[Vec<Tile>]) -> usize {
+  let vertices = trace_loop(starting_position, map);
+  let mut twice_area: i64 = 0;
+  for (i, current) in vertices.iter().enumerate() {
+    let next = vertices[(i + 1) % vertices.len()];
+    twice_area += current.0 as i64 * next.1 as i64 - next.0 as i64 * current.1 as i64;
+  }
+
+  ((twice_area.abs() - vertices.len() as i64 + 2) / 2) as usize
+}
+
+// Even-odd ray casting along each row: crossing a loop tile with a northward connection
+// flips between outside and inside.
+fn find_inside_tiles(vertices: &[Position], map: &[Vec<Tile>]) -> Vec<Vec<bool>> {
+  let mut on_loop: Vec<Vec<bool>> = map.iter().map(|row| vec![false; row.len()]).collect();
+  for (i, j) in vertices {
+    on_loop[*i][*j] = true;
+  }
+  let mut inside_tiles: Vec<Vec<bool>> = map.iter().map(|row| vec![false; row.len()]).collect();
+  for (i, row) in map.iter().enumerate() {
+    let mut inside = false;
+    for (j, tile) in row.iter().enumerate() {
+      if on_loop[i][j] {
+        if tile.connections().contains(&Direction::North) {
+          inside = !inside;
         }
       } else {
-        let distance_2 = distances.get(&(i+1, j));
-        if distance_1 != None && distance_2 != None {
-          //println!("Distance 1: {}, Distance 2: {}", distance_1.unwrap(), distance_2.unwrap());
-          let difference: i32 = distance_1.unwrap().clone() as i32 - distance_2.unwrap().clone() as i32;
-          if difference == 1 || difference == -1 {
-            inside = inside + difference;
-            //println!("Inside value: {}", inside);
+        inside_tiles[i][j] = inside;
+      }
+    }
+  }
+
+  inside_tiles
+}
+
+// Main loop in heavy lines, everything it encloses shaded solid, and the outside left as
+// light pipes over lightly shaded ground.
+fn render(starting_position: Position, map: &[Vec<Tile>]) -> usize {
+  let vertices = trace_loop(starting_position, map);
+  let distances = loop_distances(&vertices);
+  let inside_tiles = find_inside_tiles(&vertices, map);
+  let mut enclosed_tiles = 0;
+  for (i, row) in map.iter().enumerate() {
+    let line: String = row.iter().enumerate()
+      .map(|(j, tile)| {
+        if distances.contains_key(&(i, j)) {
+          tile.box_char(true)
+        } else if inside_tiles[i][j] {
+          enclosed_tiles += 1;
+          '▓'
+        } else {
+          tile.box_char(false)
+        }
+      })
+      .collect();
+    println!("{}", line);
+  }
+  println!("Loop of {} tiles enclosing {} tiles", vertices.len(), enclosed_tiles);
+
+  enclosed_tiles
+}
+
+// One square per tile: loop tiles coloured from blue at S to red at the farthest point,
+// enclosed tiles grey and the rest white.
+fn to_svg(starting_position: Position, map: &[Vec<Tile>]) -> String {
+  const SIZE: usize = 10;
+  let vertices = trace_loop(starting_position, map);
+  let distances = loop_distances(&vertices);
+  let inside_tiles = find_inside_tiles(&vertices, map);
+  let farthest = distances.values().copied().max().unwrap_or(0).max(1);
+  let width = map.iter().map(|row| row.len()).max().unwrap_or(0) * SIZE;
+  let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", width, map.len() * SIZE);
+  for (i, row) in inside_tiles.iter().enumerate() {
+    for (j, inside) in row.iter().enumerate() {
+      let fill = match distances.get(&(i, j)) {
+        Some(distance) => format!("hsl({}, 90%, 50%)", 240 - 240 * distance / farthest),
+        None if *inside => "#999999".to_owned(),
+        None => "#ffffff".to_owned()
+      };
+      svg.push_str(&format!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+        j * SIZE, i * SIZE, SIZE, SIZE, fill));
+    }
+  }
+  svg.push_str("</svg>\n");
+
+  svg
+}
+
+struct Component {
+  tiles: Vec<Position>,
+  closed: bool
+}
+
+// Neighbours that this tile points at and that point back at it.
+fn linked_neighbours(position: Position, map: &[Vec<Tile>]) -> Vec<Position> {
+  map[position.0][position.1].connections().iter()
+    .filter_map(|direction| {
+      let (i, j) = direction.step(position, map)?;
+      map[i][j].connections().contains(&direction.opposite()).then_some((i, j))
+    })
+    .collect()
+}
+
+// Groups pipe tiles joined by linked connections. Every tile has at most two links, so a
+// component is a closed loop exactly when all of its tiles have two.
+fn find_components(map: &[Vec<Tile>]) -> Vec<Component> {
+  let mut components: Vec<Component> = Vec::new();
+  let mut visited: Vec<Vec<bool>> = map.iter().map(|row| vec![false; row.len()]).collect();
+  for (i, row) in map.iter().enumerate() {
+    for (j, tile) in row.iter().enumerate() {
+      if visited[i][j] || tile.connections().is_empty() {
+        continue;
+      }
+      visited[i][j] = true;
+      let mut tiles: Vec<Position> = Vec::new();
+      let mut closed = true;
+      let mut to_visit: Vec<Position> = vec![(i, j)];
+      while let Some(position) = to_visit.pop() {
+        tiles.push(position);
+        let neighbours = linked_neighbours(position, map);
+        closed &= neighbours.len() == 2;
+        for neighbour in neighbours {
+          if !visited[neighbour.0][neighbour.1] {
+            visited[neighbour.0][neighbour.1] = true;
+            to_visit.push(neighbour);
           }
         }
       }
-      j = j + 1;
+      components.push(Component { tiles, closed });
     }
-    println!("{}", to_print.into_iter().collect::<String>());
-    i = i + 1;
   }
-  enclosed_tiles
+
+  components
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  let file: String = fs::read_to_string(&args[1]).unwrap();
+// Connections that lead off the map or into a tile that does not connect back.
+fn find_mismatches(map: &[Vec<Tile>]) -> Vec<(Position, Direction)> {
+  let mut mismatches: Vec<(Position, Direction)> = Vec::new();
+  for (i, row) in map.iter().enumerate() {
+    for (j, tile) in row.iter().enumerate() {
+      for direction in tile.connections() {
+        let linked = match direction.step((i, j), map) {
+          Some((k, l)) => map[k][l].connections().contains(&direction.opposite()),
+          None => false
+        };
+        if !linked {
+          mismatches.push(((i, j), *direction));
+        }
+      }
+    }
+  }
+
+  mismatches
+}
+
+// Works on the grid alone: a missing S or one whose shape cannot be inferred only means no
+// loop is marked as the main one.
+fn analyze(starting_position: Option<Position>, map: &[Vec<Tile>]) -> usize {
+  let components = find_components(map);
+  for (idx, component) in components.iter().enumerate() {
+    let first = component.tiles[0];
+    if component.closed {
+      let start = starting_position.filter(|p| component.tiles.contains(p));
+      let vertices = trace_loop(start.unwrap_or(first), map);
+      let farthest = vertices.len() / 2;
+      println!("Component {}: closed loop of {} tiles, farthest point from {:?} is {:?} at {} steps{}",
+        idx + 1, vertices.len(), vertices[0], vertices[farthest], farthest,
+        if start.is_some() { " (main loop)" } else { "" });
+    } else {
+      let ends: Vec<Position> = component.tiles.iter()
+        .filter(|position| linked_neighbours(**position, map).len() < 2)
+        .copied()
+        .collect();
+      println!("Component {}: dead-end chain of {} tiles, ends at {:?}", idx + 1, component.tiles.len(), ends);
+    }
+  }
+
+  let mismatches = find_mismatches(map);
+  for (position, direction) in &mismatches {
+    let tile = map[position.0][position.1];
+    match direction.step(*position, map) {
+      Some(neighbour) => println!("{:?} {:?} points {:?} at {:?} {:?}, which does not connect back",
+        position, tile, direction, map[neighbour.0][neighbour.1], neighbour),
+      None => println!("{:?} {:?} points {:?} off the map", position, tile, direction)
+    }
+  }
+  println!("{} components, {} closed loops, {} mismatched connections",
+    components.len(), components.iter().filter(|c| c.closed).count(), mismatches.len());
+
+  components.len()
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day10 <file> [a|b|render [--svg file]|analyze]")?;
+  let file: String = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
   let lines: Vec<&str> = file.lines()
     .collect();
 
-  let (starting_position, map) = parse_input(&lines);
-  let result = prob_b(starting_position, &map);
+  // Day10 <file> [a|b|analyze]
+  // Day10 <file> render [--svg file]
+  if args.get(2).is_some_and(|mode| mode == "analyze") {
+    let (starting_position, mut map) = parse_map(&lines)?;
+    match starting_position.map(|p| (p, infer_starting_tile(p, &map))) {
+      Some((p, Ok(tile))) => map[p.0][p.1] = tile,
+      Some((_, Err(e))) => println!("Cannot infer the starting tile: {}", e),
+      None => println!("There is no starting position")
+    }
+    println!("Result: {}", analyze(starting_position, &map));
+    return Ok(());
+  }
+  let (starting_position, mut map) = parse_input(&lines)?;
+  map[starting_position.0][starting_position.1] = infer_starting_tile(starting_position, &map)?;
+  let result = match args.get(2).map(|s| s.as_str()) {
+    Some("a") => prob_a(starting_position, &map),
+    Some("b") | None => prob_b(starting_position, &map),
+    Some("render") => {
+      if let Some(idx) = args.iter().position(|a| a == "--svg") {
+        let output = args.get(idx + 1).ok_or("--svg needs an output file")?;
+        fs::write(output, to_svg(starting_position, &map)).map_err(|e| format!("Could not write {}: {}", output, e))?;
+        println!("Wrote distance heatmap to {}", output);
+      }
+      render(starting_position, &map)
+    },
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
+  };
 
   println!("Result: {}", result);
-}
\ No newline at end of file
+  Ok(())
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn enclosed_tiles(file: &str) -> usize {
+    let lines: Vec<&str> = file.lines().collect();
+    let (starting_position, mut map) = parse_input(&lines).unwrap();
+    map[starting_position.0][starting_position.1] = infer_starting_tile(starting_position, &map).unwrap();
+    prob_b(starting_position, &map)
+  }
+
+  #[test]
+  fn encloses_one_tile_in_test() {
+    assert_eq!(enclosed_tiles(include_str!("../test.txt")), 1);
+  }
+
+  #[test]
+  fn encloses_four_tiles_in_test2() {
+    assert_eq!(enclosed_tiles(include_str!("../test2.txt")), 4);
+  }
+
+  #[test]
+  fn encloses_ten_tiles_in_test3() {
+    assert_eq!(enclosed_tiles(include_str!("../test3.txt")), 10);
+  }
+
+  #[test]
+  fn encloses_eight_tiles_in_test4() {
+    assert_eq!(enclosed_tiles(include_str!("../test4.txt")), 8);
+  }
+}
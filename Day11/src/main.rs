@@ -1,9 +1,10 @@
 use std::fs;
+use std::io::{BufReader, Read};
 
 type Position = (usize, usize);
 type Galaxy = (usize, Position);
 
-fn parse_input(lines: &Vec<&str>) -> Vec<Vec<usize>> {
+fn parse_input(lines: &[&str]) -> Vec<Vec<usize>> {
   let mut galaxy_number = 1;
   let mut map: Vec<Vec<usize>> = Vec::new();
   for line in lines {
@@ -14,7 +15,7 @@ fn parse_input(lines: &Vec<&str>) -> Vec<Vec<usize>> {
         '.' => file.push(0),
         '#' => {
           file.push(galaxy_number);
-          galaxy_number = galaxy_number + 1;
+          galaxy_number += 1;
         },
         _ => {
           panic!("Invalid character found: {}", c);
@@ -26,7 +27,7 @@ fn parse_input(lines: &Vec<&str>) -> Vec<Vec<usize>> {
   map
 }
 
-fn print_map(map: &Vec<Vec<usize>>) {
+fn print_map(map: &[Vec<usize>]) {
   for line in map {
     let str_line: Vec<String> = line.iter().map(|n| n.to_string()).collect();
     let to_print: String = str_line.join("");
@@ -34,7 +35,7 @@ fn print_map(map: &Vec<Vec<usize>>) {
   }
 }
 
-fn find_empty_rows_and_columns(map: &Vec<Vec<usize>>) -> (Vec<usize>, Vec<usize>) {
+fn find_empty_rows_and_columns(map: &[Vec<usize>]) -> (Vec<usize>, Vec<usize>) {
   let mut empty_rows: Vec<usize> = Vec::new();
   let mut empty_columns: Vec<usize> = Vec::new();
   // Rows
@@ -54,7 +55,7 @@ fn find_empty_rows_and_columns(map: &Vec<Vec<usize>>) -> (Vec<usize>, Vec<usize>
 
   // Columns
   let mut j = 0;
-  while j < map[0].len() {
+  while j < map.first().map_or(0, |row| row.len()) {
     let mut is_empty = true;
     let mut i = 0;
     while is_empty && i < map.len() {
@@ -72,9 +73,9 @@ fn find_empty_rows_and_columns(map: &Vec<Vec<usize>>) -> (Vec<usize>, Vec<usize>
   (empty_rows, empty_columns)
 }
 
-fn expand_map(map: &Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+fn expand_map(map: &[Vec<usize>]) -> Vec<Vec<usize>> {
   let mut expanded_map: Vec<Vec<usize>> = Vec::new();
-  let (empty_rows, empty_columns) = find_empty_rows_and_columns(&map);
+  let (empty_rows, empty_columns) = find_empty_rows_and_columns(map);
   println!("Found empty rows: {:?}", empty_rows);
   println!("Found empty columns: {:?}", empty_columns);
 
@@ -98,7 +99,7 @@ fn expand_map(map: &Vec<Vec<usize>>) -> Vec<Vec<usize>> {
   expanded_map
 }
 
-fn find_galaxies(map: &Vec<Vec<usize>>) -> Vec<Galaxy> {
+fn find_galaxies(map: &[Vec<usize>]) -> Vec<Galaxy> {
   let mut galaxies: Vec<Galaxy> = Vec::new();
   let mut i = 0;
   while i < map.len() {
@@ -115,8 +116,7 @@ fn find_galaxies(map: &Vec<Vec<usize>>) -> Vec<Galaxy> {
 }
 
 fn find_shortest_path(a: &Galaxy, b: &Galaxy) -> usize {
-  (if a.1.0 >= b.1.0 { a.1.0 - b.1.0 } else { b.1.0 - a.1.0 })
-    + (if a.1.1 >= b.1.1 { a.1.1 - b.1.1 } else { b.1.1 - a.1.1 })
+  a.1.0.abs_diff(b.1.0) + a.1.1.abs_diff(b.1.1)
 }
 
 fn number_in_between(a: usize, b: usize, n: usize) -> bool {
@@ -131,15 +131,19 @@ fn number_in_between(a: usize, b: usize, n: usize) -> bool {
 
 fn find_num_of_empty_lines_in_path(
   a: &Galaxy, b: &Galaxy,
-  empty_rows: &Vec<usize>, empty_columns: &Vec<usize>) -> usize {
+  empty_rows: &[usize], empty_columns: &[usize]) -> usize {
     let num_empty_rows: usize = empty_rows.iter().fold(0, |acc, row| if number_in_between(a.1.0, b.1.0, *row) { acc + 1 } else { acc });
     let num_empty_columns: usize = empty_columns.iter().fold(0, |acc, col| if number_in_between(a.1.1, b.1.1, *col) { acc + 1 } else { acc });
-    return num_empty_rows + num_empty_columns;
+    num_empty_rows + num_empty_columns
 }
 
-fn prob_a(map: &Vec<Vec<usize>>) -> usize {
+fn expanded_distance(a: &Galaxy, b: &Galaxy, empty_rows: &[usize], empty_columns: &[usize], factor: usize) -> usize {
+  find_shortest_path(a, b) + find_num_of_empty_lines_in_path(a, b, empty_rows, empty_columns) * (factor - 1)
+}
+
+fn prob_a(map: &[Vec<usize>]) -> usize {
   let mut sum = 0;
-  let galaxies = find_galaxies(&map);
+  let galaxies = find_galaxies(map);
   let mut pairs: usize = 0;
   let mut idx = 0;
   while idx < galaxies.len() {
@@ -156,18 +160,17 @@ fn prob_a(map: &Vec<Vec<usize>>) -> usize {
   sum
 }
 
-fn prob_b(map: &Vec<Vec<usize>>) -> usize {
+fn prob_b(map: &[Vec<usize>], factor: usize) -> usize {
   let mut sum = 0;
-  let (empty_rows, empty_columns) = find_empty_rows_and_columns(&map);
-  let galaxies = find_galaxies(&map);
+  let (empty_rows, empty_columns) = find_empty_rows_and_columns(map);
+  let galaxies = find_galaxies(map);
   let mut pairs: usize = 0;
   let mut idx = 0;
   while idx < galaxies.len() {
     let mut j = idx + 1;
     while j < galaxies.len() {
       pairs += 1;
-      sum += find_shortest_path(&galaxies[idx], &galaxies[j]);
-      sum += find_num_of_empty_lines_in_path(&galaxies[idx], &galaxies[j], &empty_rows, &empty_columns) * (1000000 - 1);
+      sum += expanded_distance(&galaxies[idx], &galaxies[j], &empty_rows, &empty_columns, factor);
       j += 1;
     }
     idx += 1;
@@ -177,16 +180,229 @@ fn prob_b(map: &Vec<Vec<usize>>) -> usize {
   sum
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  let file: String = fs::read_to_string(&args[1]).unwrap();
-  let lines: Vec<&str> = file.lines()
+// Only the galaxies and the size of the sky, so memory grows with the galaxy count
+// rather than with the area of the map.
+struct Sky {
+  width: usize,
+  height: usize,
+  galaxies: Vec<Galaxy>
+}
+
+impl Sky {
+  // Streams the file byte by byte, so not even a single row has to fit in memory.
+  fn read(path: &str) -> Result<Sky, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut sky = Sky { width: 0, height: 0, galaxies: Vec::new() };
+    let (mut i, mut j) = (0, 0);
+    for byte in BufReader::new(file).bytes() {
+      match byte.map_err(|e| format!("Could not read {}: {}", path, e))? {
+        b'.' => j += 1,
+        b'#' => {
+          sky.galaxies.push((sky.galaxies.len() + 1, (i, j)));
+          j += 1;
+        },
+        b'\n' => {
+          sky.width = sky.width.max(j);
+          i += 1;
+          j = 0;
+        },
+        b'\r' => (),
+        c => return Err(format!("Line {}: Invalid character found: {}", i + 1, c as char))
+      }
+    }
+    sky.width = sky.width.max(j);
+    sky.height = if j > 0 { i + 1 } else { i };
+
+    Ok(sky)
+  }
+
+  // Each galaxy moved by (factor - 1) for every empty row above it and empty column to its
+  // left. A coordinate c has (c - occupied coordinates below c) empty lines before it.
+  fn expand(&self, factor: usize) -> Result<Vec<Galaxy>, String> {
+    let mut rows: Vec<usize> = self.galaxies.iter().map(|g| g.1.0).collect();
+    let mut columns: Vec<usize> = self.galaxies.iter().map(|g| g.1.1).collect();
+    rows.sort_unstable();
+    rows.dedup();
+    columns.sort_unstable();
+    columns.dedup();
+    let expand = |occupied: &[usize], c: usize| (c - occupied.partition_point(|o| *o < c))
+      .checked_mul(factor - 1)
+      .and_then(|growth| growth.checked_add(c))
+      .ok_or(format!("Expanding by a factor of {} overflows", factor));
+    self.galaxies.iter()
+      .map(|(n, (i, j))| Ok((*n, (expand(&rows, *i)?, expand(&columns, *j)?))))
+      .collect()
+  }
+}
+
+// Sum of |x_i - x_j| over all pairs: once sorted, the i-th value is larger than the i
+// before it, so it contributes x_i * i minus the sum of those values.
+fn sum_of_pairwise_distances(sorted: &[usize]) -> Option<usize> {
+  let mut sum: usize = 0;
+  let mut prefix: usize = 0;
+  for (i, x) in sorted.iter().enumerate() {
+    sum = sum.checked_add(x.checked_mul(i)? - prefix)?;
+    prefix = prefix.checked_add(*x)?;
+  }
+  Some(sum)
+}
+
+// The Manhattan distance splits into a row part and a column part, so the all-pairs sum
+// is two one-dimensional sums over sorted expanded coordinates.
+fn sum_distances(sky: &Sky, factor: usize) -> Result<usize, String> {
+  let galaxies = sky.expand(factor)?;
+  let mut rows: Vec<usize> = galaxies.iter().map(|g| g.1.0).collect();
+  let mut columns: Vec<usize> = galaxies.iter().map(|g| g.1.1).collect();
+  rows.sort_unstable();
+  columns.sort_unstable();
+
+  sum_of_pairwise_distances(&rows)
+    .zip(sum_of_pairwise_distances(&columns))
+    .and_then(|(rows, columns)| rows.checked_add(columns))
+    .ok_or(format!("The sum of distances at a factor of {} overflows", factor))
+}
+
+fn check(map: &[Vec<usize>], sky: &Sky, factor: usize) -> Result<(), String> {
+  if sky.galaxies != find_galaxies(map) || sky.height != map.len() || sky.width != map.first().map_or(0, |row| row.len()) {
+    return Err("The streaming parser does not find the same galaxies as parse_input".to_owned());
+  }
+  let expanded = prob_a(&expand_map(map));
+  let fast = sum_distances(sky, 2)?;
+  if expanded != fast {
+    return Err(format!("Factor 2: expand_map gives {} but the prefix sums give {}", expanded, fast));
+  }
+  // A sum that fits also fits every partial sum of the pair loop
+  let fast = sum_distances(sky, factor)?;
+  let pairwise = prob_b(map, factor);
+  if pairwise != fast {
+    return Err(format!("Factor {}: the pair loop gives {} but the prefix sums give {}", factor, pairwise, fast));
+  }
+  println!("Factor 2: {}, factor {}: {}", expanded, factor, pairwise);
+  Ok(())
+}
+
+fn find_galaxy(galaxies: &[Galaxy], number: &str) -> Result<Galaxy, String> {
+  let number = number.parse::<usize>().map_err(|_| format!("Invalid galaxy number {}", number))?;
+  galaxies.iter()
+    .find(|g| g.0 == number)
+    .copied()
+    .ok_or(format!("There is no galaxy {}, the map has {}", number, galaxies.len()))
+}
+
+// Every galaxy's distance to every other one, in galaxy number order.
+fn distance_matrix(sky: &Sky, factor: usize) -> Result<(Vec<Galaxy>, Vec<Vec<usize>>), String> {
+  let galaxies = sky.expand(factor)?;
+  let matrix = galaxies.iter()
+    .map(|a| galaxies.iter().map(|b| find_shortest_path(a, b)).collect())
     .collect();
+  Ok((sky.galaxies.clone(), matrix))
+}
+
+fn print_neighbours(sky: &Sky, factor: usize) -> Result<(), String> {
+  let (galaxies, matrix) = distance_matrix(sky, factor)?;
+  for (idx, galaxy) in galaxies.iter().enumerate() {
+    let others = || matrix[idx].iter().enumerate().filter(|(j, _)| *j != idx);
+    let (Some(nearest), Some(farthest)) = (others().min_by_key(|(_, d)| **d), others().max_by_key(|(_, d)| **d)) else {
+      println!("Galaxy {} at {:?} has no neighbours", galaxy.0, galaxy.1);
+      continue;
+    };
+    println!("Galaxy {} at {:?}: nearest is {} ({}), farthest is {} ({})",
+      galaxy.0, galaxy.1, galaxies[nearest.0].0, nearest.1, galaxies[farthest.0].0, farthest.1);
+  }
+  Ok(())
+}
+
+fn to_csv(sky: &Sky, factor: usize) -> Result<String, String> {
+  let (galaxies, matrix) = distance_matrix(sky, factor)?;
+  let mut csv = String::from("galaxy");
+  for galaxy in &galaxies {
+    csv.push_str(&format!(",{}", galaxy.0));
+  }
+  csv.push('\n');
+  for (galaxy, row) in galaxies.iter().zip(&matrix) {
+    csv.push_str(&galaxy.0.to_string());
+    for distance in row {
+      csv.push_str(&format!(",{}", distance));
+    }
+    csv.push('\n');
+  }
+  Ok(csv)
+}
+
+fn parse_factor(arg: Option<&String>, default: usize) -> Result<usize, String> {
+  match arg {
+    Some(factor) => match factor.parse::<usize>() {
+      Ok(factor) if factor > 0 => Ok(factor),
+      _ => Err(format!("Invalid expansion factor {}", factor))
+    },
+    None => Ok(default)
+  }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day11 <file> [a|b|factor <k>|check [k]|map|distance <i> <j>|neighbours|csv <file>] [--factor k]")?;
+
+  // Day11 <file> [a|b]
+  // Day11 <file> factor <k>
+  // Day11 <file> check [k]
+  // Day11 <file> map
+  // Day11 <file> distance <galaxy> <galaxy> [--factor k]
+  // Day11 <file> neighbours [--factor k]
+  // Day11 <file> csv <output file> [--factor k]
+  let mut positional: Vec<String> = Vec::new();
+  let mut factor = 2;
+  let mut iter = args[2..].iter();
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--factor" => factor = parse_factor(Some(iter.next().ok_or("--factor needs a number")?), factor)?,
+      _ => positional.push(arg.clone())
+    }
+  }
+  // Only the check and map modes need the dense map
+  let dense_map = || -> Result<Vec<Vec<usize>>, String> {
+    let file: String = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let lines: Vec<&str> = file.lines()
+      .collect();
+    Ok(parse_input(&lines))
+  };
+  let sky = Sky::read(path)?;
+  match positional.first().map(|s| s.as_str()) {
+    None => {
+      println!("Result: {}", sum_distances(&sky, 2)?);
+      println!("Result: {}", sum_distances(&sky, 1000000)?);
+    },
+    Some("a") => println!("Result: {}", sum_distances(&sky, 2)?),
+    Some("b") => println!("Result: {}", sum_distances(&sky, 1000000)?),
+    Some("factor") => {
+      let factor = parse_factor(positional.get(1), factor)?;
+      println!("Result: {}", sum_distances(&sky, factor)?);
+    },
+    Some("check") => check(&dense_map()?, &sky, parse_factor(positional.get(1), 1000000)?)?,
+    Some("map") => print_map(&expand_map(&dense_map()?)),
+    Some("distance") => {
+      let (Some(a), Some(b)) = (positional.get(1), positional.get(2)) else {
+        return Err("distance needs two galaxy numbers".to_owned());
+      };
+      let galaxies = sky.expand(factor)?;
+      let (a, b) = (find_galaxy(&galaxies, a)?, find_galaxy(&galaxies, b)?);
+      println!("Result: {}", find_shortest_path(&a, &b));
+    },
+    Some("neighbours") => print_neighbours(&sky, factor)?,
+    Some("csv") => {
+      let output = positional.get(1).ok_or("csv needs an output file")?;
+      fs::write(output, to_csv(&sky, factor)?).map_err(|e| format!("Could not write {}: {}", output, e))?;
+      println!("Wrote distance matrix to {}", output);
+    },
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
+  }
 
-  let map = parse_input(&lines);
-  let expanded_map = expand_map(&map);
-  let result_a = prob_a(&expanded_map);
-  println!("Result: {}", result_a);
-  let result_b = prob_b(&map);
-  println!("Result: {}", result_b);
-}
\ No newline at end of file
+  Ok(())
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
@@ -8,118 +8,299 @@ struct Map {
   range_length: usize
 }
 
-struct EquivalenceMaps {
-  seed_to_soil: Vec<Map>,
-  soil_to_fertilizer: Vec<Map>,
-  fertilizer_to_water: Vec<Map>,
-  water_to_light: Vec<Map>,
-  light_to_temperature: Vec<Map>,
-  temperature_to_humidity: Vec<Map>,
-  humidity_to_location: Vec<Map>
+impl Map {
+  fn apply(&self, value: usize) -> Option<usize> {
+    if value >= self.source_range_start && value < self.source_range_start + self.range_length {
+      Some(self.destination_range_start + (value - self.source_range_start))
+    } else {
+      None
+    }
+  }
+
+  fn invert(&self, value: usize) -> Option<usize> {
+    if value >= self.destination_range_start && value < self.destination_range_start + self.range_length {
+      Some(self.source_range_start + (value - self.destination_range_start))
+    } else {
+      None
+    }
+  }
 }
 
-impl EquivalenceMaps {
-  fn iter(&self) -> Vec<&Vec<Map>> {
-    vec![&self.seed_to_soil, &self.soil_to_fertilizer, &self.fertilizer_to_water, &self.water_to_light, &self.light_to_temperature, &self.temperature_to_humidity, &self.humidity_to_location]
+#[derive(Clone, Copy)]
+struct Segment {
+  start: usize,
+  end: usize,
+  offset: isize
+}
+
+impl Segment {
+  fn apply(&self, value: usize) -> usize {
+    value.saturating_add_signed(self.offset)
   }
 }
 
-fn parse_map(input: &str) -> Vec<Map> {
-  let mut result = Vec::new();
-  let maps: Vec<&str> = input.split("\n").collect();
-  for map in maps {
-    let parsed: Vec<usize> = map.split(" ")
-      .map(|n| n.parse::<usize>().unwrap())
+// All stages folded into one: sorted, non-overlapping segments covering every source value
+struct ComposedMap {
+  source: String,
+  destination: String,
+  segments: Vec<Segment>
+}
+
+impl ComposedMap {
+  fn lookup(&self, value: usize) -> usize {
+    let idx = self.segments.partition_point(|s| s.end <= value);
+    match self.segments.get(idx) {
+      Some(segment) if segment.start <= value => segment.apply(value),
+      _ => value
+    }
+  }
+
+  // The segments turned around, sorted by the locations they produce and mapping each
+  // location back to its seed
+  fn invert(&self) -> Vec<Segment> {
+    let mut inverted: Vec<Segment> = self.segments.iter()
+      .map(|segment| {
+        let start = segment.apply(segment.start);
+        Segment { start, end: start.saturating_add(segment.end - segment.start), offset: -segment.offset }
+      })
       .collect();
-    if parsed.len() < 3 {
-      panic!("Cannot parse map");
+    inverted.sort_by_key(|segment| segment.start);
+
+    inverted
+  }
+
+  fn print_table(&self) {
+    println!("{:>20} {:>20} {:>14} {:>20} {:>20}", format!("{} start", self.source), format!("{} end", self.source), "offset", format!("{} start", self.destination), format!("{} end", self.destination));
+    for segment in &self.segments {
+      println!("{:>20} {:>20} {:>14} {:>20} {:>20}", segment.start, segment.end - 1, segment.offset, segment.apply(segment.start), segment.apply(segment.end - 1));
     }
-    result.push(Map {
-      source_range_start: parsed[1],
-      destination_range_start: parsed[0],
-      range_length: parsed[2]
-    });
+  }
+}
+
+struct Stage {
+  source: String,
+  destination: String,
+  maps: Vec<Map>
+}
+
+impl Stage {
+  fn apply(&self, value: usize) -> usize {
+    self.maps.iter()
+      .find_map(|map| map.apply(value))
+      .unwrap_or(value)
   }
 
-  result
-}
-
-fn parse_input(file: &str) -> (Vec<usize>, EquivalenceMaps) {
-  let re = Regex::new(r"seeds: (?<seeds>[\d\s]+)\n\nseed-to-soil map:\n(?<se2so>[\d\s\n]+)\n\nsoil-to-fertilizer map:\n(?<so2f>[\d\s\n]+)\n\nfertilizer-to-water map:\n(?<f2w>[\d\s\n]+)\n\nwater-to-light map:\n(?<w2l>[\d\s\n]+)\n\nlight-to-temperature map:\n(?<l2t>[\d\s\n]+)\n\ntemperature-to-humidity map:\n(?<t2h>[\d\s\n]+)\n\nhumidity-to-location map:\n(?<h2l>[\d\s\n]+)").unwrap();
-  match re.captures(file) {
-      Some(captures) => {
-        let seeds: Vec<usize> = (&captures["seeds"])
-          .split(" ")
-          .map(|n| n.parse::<usize>().unwrap()).collect();
-        return (seeds, EquivalenceMaps {
-          seed_to_soil: parse_map(&captures["se2so"]),
-          soil_to_fertilizer: parse_map(&captures["so2f"]),
-          fertilizer_to_water: parse_map(&captures["f2w"]),
-          water_to_light: parse_map(&captures["w2l"]),
-          light_to_temperature: parse_map(&captures["l2t"]),
-          temperature_to_humidity: parse_map(&captures["t2h"]),
-          humidity_to_location: parse_map(&captures["h2l"])
-        });
-      },
-      None => panic!("Could not parse card")
-  }
-}
-
-fn find_location_from_seed(seed: usize, eq_maps: &EquivalenceMaps) -> usize {
-  let mut value = seed;
-  for eq in eq_maps.iter() {
-    for map in eq {
-      if value >= map.source_range_start && value < map.source_range_start + map.range_length {
-        value = map.destination_range_start + (value - map.source_range_start);
-        break;
+  fn validate(&mut self) -> Result<(), String> {
+    self.maps.sort_by_key(|map| map.source_range_start);
+    for pair in self.maps.windows(2) {
+      if pair[0].source_range_start + pair[0].range_length > pair[1].source_range_start {
+        return Err(format!(
+          "Stage {}-to-{} has overlapping source ranges {}[{}] and {}[{}]",
+          self.source, self.destination,
+          pair[0].source_range_start, pair[0].range_length, pair[1].source_range_start, pair[1].range_length
+        ));
       }
     }
+
+    Ok(())
+  }
+
+  // Maps sorted by source with the identity gaps between them filled in
+  fn segments(&self) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut cursor = 0;
+    for map in self.maps.iter().filter(|map| map.range_length > 0) {
+      if map.source_range_start > cursor {
+        segments.push(Segment { start: cursor, end: map.source_range_start, offset: 0 });
+      }
+      segments.push(Segment {
+        start: map.source_range_start,
+        end: map.source_range_start + map.range_length,
+        offset: map.destination_range_start as isize - map.source_range_start as isize
+      });
+      cursor = map.source_range_start + map.range_length;
+    }
+    if cursor < usize::MAX {
+      segments.push(Segment { start: cursor, end: usize::MAX, offset: 0 });
+    }
+
+    segments
   }
 
-  value
+  // A value can have several preimages: one per map whose destination covers it, plus
+  // itself when no source range covers it and it falls through unchanged.
+  fn invert(&self, value: usize) -> Vec<usize> {
+    let mut preimages: Vec<usize> = self.maps.iter()
+      .filter_map(|map| map.invert(value))
+      .collect();
+    if self.maps.iter().all(|map| map.apply(value).is_none()) {
+      preimages.push(value);
+    }
+
+    preimages
+  }
 }
 
-fn find_location_from_seed_range(seed_range: (usize, usize), eq_maps: &EquivalenceMaps) -> usize {
-  println!("Finding location from seed range {}[{}]", seed_range.0, seed_range.1);
-  let mut location_values: Vec<usize> = Vec::new();
-  let from = seed_range.0;
-  let to = seed_range.0 + seed_range.1;
-  for initial_value in from..to {
-    println!("Finding location from seed value {}", initial_value);
-    let mut value = initial_value;
-    for eq in eq_maps.iter() {
-      for map in eq {
-        if value >= map.source_range_start && value < map.source_range_start + map.range_length {
-          value = map.destination_range_start + (value - map.source_range_start);
-          break;
+struct EquivalenceMaps {
+  stages: Vec<Stage>
+}
+
+impl EquivalenceMaps {
+  fn new(mut stages: Vec<Stage>) -> Result<EquivalenceMaps, String> {
+    for stage in stages.iter_mut() {
+      stage.validate()?;
+    }
+    for pair in stages.windows(2) {
+      if pair[0].destination != pair[1].source {
+        return Err(format!(
+          "Stage {}-to-{} is followed by {}-to-{}, expected a stage starting at {}",
+          pair[0].source, pair[0].destination, pair[1].source, pair[1].destination, pair[0].destination
+        ));
+      }
+    }
+
+    Ok(EquivalenceMaps { stages })
+  }
+
+  fn first_category(&self) -> &str {
+    self.stages.first().map(|s| s.source.as_str()).unwrap_or("seed")
+  }
+
+  fn last_category(&self) -> &str {
+    self.stages.last().map(|s| s.destination.as_str()).unwrap_or("seed")
+  }
+
+  fn path(&self, from: &str, to: &str) -> Result<&[Stage], String> {
+    let start = match self.stages.iter().position(|s| s.source == from) {
+      Some(start) => start,
+      None if from == to && self.last_category() == to => return Ok(&[]),
+      None => return Err(format!("No stage maps from category {}", from))
+    };
+    if from == to {
+      return Ok(&[]);
+    }
+    match self.stages[start..].iter().position(|s| s.destination == to) {
+      Some(len) => Ok(&self.stages[start..start + len + 1]),
+      None => Err(format!("Category {} cannot be reached from {}", to, from))
+    }
+  }
+
+  fn convert(&self, value: usize, from: &str, to: &str) -> Result<usize, String> {
+    let path = self.path(from, to)?;
+    Ok(path.iter().fold(value, |v, stage| stage.apply(v)))
+  }
+
+  fn find_location(&self, seed: usize) -> usize {
+    self.stages.iter().fold(seed, |v, stage| stage.apply(v))
+  }
+
+  fn compose(&self) -> ComposedMap {
+    let mut segments = vec![Segment { start: 0, end: usize::MAX, offset: 0 }];
+    for stage in &self.stages {
+      let pieces = stage.segments();
+      let mut composed: Vec<Segment> = Vec::new();
+      for segment in &segments {
+        let out_start = segment.apply(segment.start);
+        let out_end = out_start.saturating_add(segment.end - segment.start);
+        let mut idx = pieces.partition_point(|p| p.end <= out_start);
+        while idx < pieces.len() && pieces[idx].start < out_end {
+          let piece = &pieces[idx];
+          let from = piece.start.max(out_start);
+          let to = piece.end.min(out_end);
+          let next = Segment {
+            start: segment.start + (from - out_start),
+            end: segment.start + (to - out_start),
+            offset: segment.offset + piece.offset
+          };
+          match composed.last_mut() {
+            Some(last) if last.end == next.start && last.offset == next.offset => last.end = next.end,
+            _ => composed.push(next)
+          }
+          idx += 1;
         }
       }
+      segments = composed;
+    }
+
+    ComposedMap {
+      source: self.first_category().to_owned(),
+      destination: self.last_category().to_owned(),
+      segments
+    }
+  }
+
+  fn find_seeds(&self, location: usize) -> Vec<usize> {
+    let mut values = vec![location];
+    for stage in self.stages.iter().rev() {
+      values = values.iter()
+        .flat_map(|v| stage.invert(*v))
+        .collect();
+    }
+    values.sort();
+    values.dedup();
+
+    values
+  }
+}
+
+fn parse_map(input: &str) -> Result<Vec<Map>, String> {
+  let mut result = Vec::new();
+  for map in input.lines().filter(|l| !l.trim().is_empty()) {
+    let parsed: Vec<usize> = map.split_whitespace()
+      .map(|n| n.parse::<usize>().map_err(|_| format!("\"{}\" in map \"{}\" is not a number", n, map.trim())))
+      .collect::<Result<Vec<usize>, String>>()?;
+    if parsed.len() != 3 {
+      return Err(format!("Map \"{}\" does not have a destination, source and length", map.trim()));
     }
-    location_values.push(value);
+    result.push(Map {
+      source_range_start: parsed[1],
+      destination_range_start: parsed[0],
+      range_length: parsed[2]
+    });
   }
 
-  find_lowest_value(location_values)
+  Ok(result)
 }
 
-fn find_location_from_seed_range_par(seed_range: (usize, usize), eq_maps: &EquivalenceMaps) -> usize {
+fn parse_stage(input: &str) -> Result<Stage, String> {
+  let re = Regex::new(r"^(?<source>\w+)-to-(?<destination>\w+) map:$").unwrap();
+  let (header, body) = input.split_once('\n').unwrap_or((input, ""));
+  match re.captures(header.trim()) {
+    Some(captures) => Ok(Stage {
+      source: captures["source"].to_owned(),
+      destination: captures["destination"].to_owned(),
+      maps: parse_map(body).map_err(|e| format!("{}: {}", header.trim().trim_end_matches(':'), e))?
+    }),
+    None => Err(format!("Could not parse map header \"{}\"", header.trim()))
+  }
+}
+
+fn parse_input(file: &str) -> Result<(Vec<usize>, EquivalenceMaps), String> {
+  let file = file.replace("\r\n", "\n");
+  let mut sections = file.split("\n\n").filter(|s| !s.trim().is_empty());
+  let seeds: Vec<usize> = match sections.next().and_then(|s| s.trim().strip_prefix("seeds:")) {
+    Some(seeds) => seeds
+      .split_whitespace()
+      .map(|n| n.parse::<usize>().map_err(|_| format!("Seed \"{}\" is not a number", n)))
+      .collect::<Result<Vec<usize>, String>>()?,
+    None => return Err("Could not parse seeds".to_owned())
+  };
+  let stages = sections
+    .map(parse_stage)
+    .collect::<Result<Vec<Stage>, String>>()?;
+
+  Ok((seeds, EquivalenceMaps::new(stages)?))
+}
+
+fn find_location_from_seed_range_par(seed_range: (usize, usize), composed: &ComposedMap) -> usize {
   println!("Finding location from seed range {}[{}]", seed_range.0, seed_range.1);
-  
+
   let from = seed_range.0;
   let to = seed_range.0 + seed_range.1;
-  let location_values: Vec<usize> = (from..to).into_par_iter().map(|initial_value| {
-    let mut value = initial_value;
-    for eq in eq_maps.iter() {
-      for map in eq {
-        if value >= map.source_range_start && value < map.source_range_start + map.range_length {
-          value = map.destination_range_start + (value - map.source_range_start);
-          break;
-        }
-      }
-    }
-    value
-  }).collect();
-
-  find_lowest_value(location_values)
+  (from..to).into_par_iter()
+    .map(|initial_value| composed.lookup(initial_value))
+    .min()
+    .unwrap_or(usize::MAX)
 }
 
 fn find_lowest_value(values: Vec<usize>) -> usize {
@@ -133,7 +314,7 @@ fn find_lowest_value(values: Vec<usize>) -> usize {
   lowest
 }
 
-fn get_seed_ranges(seeds: &Vec<usize>) -> Vec<(usize, usize)> {
+fn get_seed_ranges(seeds: &[usize]) -> Vec<(usize, usize)> {
   let mut seed_ranges: Vec<(usize, usize)> = Vec::new();
   let mut start_num: usize = 0;
   let mut is_range = false;
@@ -153,7 +334,7 @@ fn get_seed_ranges(seeds: &Vec<usize>) -> Vec<(usize, usize)> {
 fn prob_a(seeds: Vec<usize>, eq_maps: &EquivalenceMaps) -> usize {
   let mut locations = Vec::new();
   for seed in seeds {
-    locations.push(find_location_from_seed(seed, &eq_maps));
+    locations.push(eq_maps.find_location(seed));
   }
 
   find_lowest_value(locations)
@@ -162,20 +343,93 @@ fn prob_a(seeds: Vec<usize>, eq_maps: &EquivalenceMaps) -> usize {
 fn prob_b(seeds: Vec<usize>, eq_maps: &EquivalenceMaps) -> usize {
   let mut locations = Vec::new();
   let seed_ranges = get_seed_ranges(&seeds);
+  let composed = eq_maps.compose();
   for seed_range in seed_ranges {
-    locations.push(find_location_from_seed_range_par(seed_range, &eq_maps));
+    locations.push(find_location_from_seed_range_par(seed_range, &composed));
   }
 
   find_lowest_value(locations)
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  let binding = fs::read_to_string(&args[1]).unwrap();
+// Walk locations upwards and stop at the first one that comes from a seed in the ranges
+// Walks the composed map backwards from the lowest locations: each inverted segment names the
+// seeds that produce its locations, so the first one overlapping a seed range gives the answer.
+fn prob_b_reverse(seeds: Vec<usize>, eq_maps: &EquivalenceMaps) -> Option<usize> {
+  let seed_ranges = get_seed_ranges(&seeds);
+  let mut best: Option<usize> = None;
+  for segment in eq_maps.compose().invert() {
+    // Every location from here on is at least segment.start
+    if best.is_some_and(|best| segment.start >= best) {
+      break;
+    }
+    let seeds_start = segment.apply(segment.start);
+    let seeds_end = seeds_start.saturating_add(segment.end - segment.start);
+    for (start, length) in &seed_ranges {
+      let from = seeds_start.max(*start);
+      if from < seeds_end.min(start + length) {
+        let location = segment.start + (from - seeds_start);
+        best = Some(best.map_or(location, |best| best.min(location)));
+      }
+    }
+  }
+
+  best
+}
+
+fn reverse(args: &[String], eq_maps: &EquivalenceMaps) -> Result<(), String> {
+  for n in args {
+    let location = n.parse::<usize>().map_err(|_| format!("Invalid location {}", n))?;
+    println!("Location {} <- seeds {:?}", location, eq_maps.find_seeds(location));
+  }
+
+  Ok(())
+}
+
+fn convert(args: &[String], seeds: &[usize], eq_maps: &EquivalenceMaps) -> Result<(), String> {
+  let from = args.first().map(|s| s.as_str()).unwrap_or(eq_maps.first_category());
+  let to = args.get(1).map(|s| s.as_str()).unwrap_or(eq_maps.last_category());
+  let values: Vec<usize> = if args.len() > 2 {
+    args[2..].iter()
+      .map(|n| n.parse::<usize>().map_err(|_| format!("Invalid value {}", n)))
+      .collect::<Result<Vec<usize>, String>>()?
+  } else {
+    seeds.to_vec()
+  };
+  for value in values {
+    println!("{} {} -> {} {}", from, value, to, eq_maps.convert(value, from, to)?);
+  }
+
+  Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day5 <file> [a|b|convert [from] [to] [values]|reverse <locations>|search|compose]")?;
+  let binding = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
   let file: &str = binding.as_str();
 
-  let (seeds, eq_maps) = parse_input(file);
-  let result = prob_b(seeds, &eq_maps);
+  let (seeds, eq_maps) = parse_input(file)?;
+  match args.get(2).map(|s| s.as_str()) {
+    Some("a") => println!("Result: {}", prob_a(seeds, &eq_maps)),
+    Some("b") | None => println!("Result: {}", prob_b(seeds, &eq_maps)),
+    // convert [from] [to] [values...]: map values (the seeds by default) between any two categories
+    Some("convert") => convert(&args[3..], &seeds, &eq_maps)?,
+    // reverse <locations...>: list every seed that ends up at each location
+    Some("reverse") => reverse(&args[3..], &eq_maps)?,
+    Some("search") => match prob_b_reverse(seeds, &eq_maps) {
+      Some(result) => println!("Result: {}", result),
+      None => return Err("No location comes from a seed in the seed ranges".to_owned())
+    },
+    Some("compose") => eq_maps.compose().print_table(),
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
+  }
+
+  Ok(())
+}
 
-  println!("Result: {}", result);
-}
\ No newline at end of file
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
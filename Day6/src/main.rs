@@ -1,92 +1,362 @@
 use std::fs;
 use regex::Regex;
+use num::{BigInt, BigUint, One, Zero};
+
+#[derive(Clone, Copy, Debug)]
+struct Boat {
+  acceleration: usize,
+  max_speed: Option<usize>
+}
+
+impl Boat {
+  fn speed(&self, hold: &BigUint) -> BigUint {
+    let speed = hold * self.acceleration;
+    match self.max_speed {
+      Some(max_speed) => speed.min(BigUint::from(max_speed)),
+      None => speed
+    }
+  }
+
+  // First hold time at which the boat reaches its top speed
+  fn capped_from(&self) -> Option<usize> {
+    match self.max_speed {
+      Some(_) if self.acceleration == 0 => None,
+      Some(max_speed) => Some(max_speed.div_ceil(self.acceleration)),
+      None => None
+    }
+  }
+}
+
+impl Default for Boat {
+  fn default() -> Boat {
+    Boat { acceleration: 1, max_speed: None }
+  }
+}
 
 struct Race {
-  time: usize,
-  record: usize
-}
-
-fn parse_input(file: &str) -> Vec<Race> {
-  let re = Regex::new(r"Time:\s+(?<time>[\d\s]+)\nDistance:\s+(?<distance>[\d\s]+)").unwrap();
-  match re.captures(file) {
-      Some(captures) => {
-        let times: Vec<usize> = (&captures["time"])
-          .split(" ")
-          .filter(|s| !s.is_empty())
-          .map(|n| n.parse::<usize>().unwrap()).collect();
-        let distances: Vec<usize> = (&captures["distance"])
-          .split(" ")
-          .filter(|s| !s.is_empty())
-          .map(|n| n.parse::<usize>().unwrap()).collect();
-        if times.len() != distances.len() {
-          panic!("Inconsistent number of races!");
-        }
-        let mut races: Vec<Race> = Vec::new();
-        let mut idx = 0;
-        while idx < times.len() {
-          races.push(Race {
-            time: times[idx],
-            record: distances[idx]
-          });
-          idx = idx + 1;
-        }
+  time: BigUint,
+  record: BigUint,
+  boat: Boat
+}
+
+impl Race {
+  fn distance(&self, hold: &BigUint) -> BigUint {
+    self.boat.speed(hold) * (&self.time - hold)
+  }
+}
+
+struct RaceStrategy {
+  hold_times: Option<(BigUint, BigUint)>,
+  best_hold: BigUint,
+  best_distance: BigUint
+}
+
+impl RaceStrategy {
+  fn ways_to_win(&self) -> BigUint {
+    match &self.hold_times {
+      Some((min_hold, max_hold)) => max_hold - min_hold + 1u32,
+      None => BigUint::zero()
+    }
+  }
+}
+
+struct RaceSheetLine<'a> {
+  line: usize,
+  values: Vec<&'a str>
+}
+
+fn parse_sheet_line<'a>(lines: &[&'a str], label: &str) -> Result<RaceSheetLine<'a>, String> {
+  let re = Regex::new(r"^(?<label>\w+):(?<values>.*)$").unwrap();
+  for (idx, line) in lines.iter().enumerate() {
+    if let Some(captures) = re.captures(line.trim()) {
+      if &captures["label"] != label {
+        continue;
+      }
+      let values: Vec<&str> = captures.name("values").unwrap().as_str().split_whitespace().collect();
+      if let Some(value) = values.iter().find(|v| !v.chars().all(|c| c.is_ascii_digit())) {
+        return Err(format!("Line {}: \"{}\" is not a number", idx + 1, value));
+      }
+
+      return Ok(RaceSheetLine { line: idx + 1, values });
+    }
+  }
+
+  Err(format!("Missing \"{}:\" line", label))
+}
+
+fn parse_sheet(file: &str) -> Result<(RaceSheetLine<'_>, RaceSheetLine<'_>), String> {
+  let lines: Vec<&str> = file.lines().collect();
+  let times = parse_sheet_line(&lines, "Time")?;
+  let distances = parse_sheet_line(&lines, "Distance")?;
+  if times.values.len() != distances.values.len() {
+    return Err(format!(
+      "Line {}: {} distances, but line {} has {} times",
+      distances.line, distances.values.len(), times.line, times.values.len()
+    ));
+  }
+
+  Ok((times, distances))
+}
+
+// Every column of the sheet is its own race
+fn parse_races(file: &str, boat: Boat) -> Result<Vec<Race>, String> {
+  let (times, distances) = parse_sheet(file)?;
+  Ok(times.values.iter().zip(distances.values.iter())
+    .map(|(time, record)| Race {
+      time: time.parse::<BigUint>().unwrap(),
+      record: record.parse::<BigUint>().unwrap(),
+      boat
+    })
+    .collect())
+}
+
+// Bad kerning: the whitespace between numbers is meaningless and the sheet is one single race
+fn parse_single_race(file: &str, boat: Boat) -> Result<Race, String> {
+  let (times, distances) = parse_sheet(file)?;
+  if times.values.is_empty() {
+    return Err(format!("Line {}: no race times", times.line));
+  }
+
+  Ok(Race {
+    time: times.values.concat().parse::<BigUint>().unwrap(),
+    record: distances.values.concat().parse::<BigUint>().unwrap(),
+    boat
+  })
+}
+
+// Before the boat reaches top speed, holding for h ms travels a * h * (time - h), so the winning
+// hold times are the integers strictly between the roots of a * h^2 - a * time * h + record. The
+// integer square root only gives an estimate of the lower root, which is then corrected against
+// the exact distance.
+fn find_uncapped_hold_times(race: &Race) -> Option<(BigUint, BigUint)> {
+  let time = &race.time;
+  let record = &race.record;
+  let acceleration = BigUint::from(race.boat.acceleration);
+  if acceleration.is_zero() || &acceleration * time * time < record * 4u32 {
+    return None;
+  }
+  let root = (&acceleration * &acceleration * time * time - &acceleration * record * 4u32).sqrt();
+  let mut min_hold = (&acceleration * time - root) / (&acceleration * 2u32);
+  let beats_record = |hold: &BigUint| &acceleration * hold * (time - hold) > *record;
+  let half_time = time / 2u32;
+  while !min_hold.is_zero() && beats_record(&(&min_hold - 1u32)) {
+    min_hold -= 1u32;
+  }
+  while min_hold <= half_time && !beats_record(&min_hold) {
+    min_hold += 1u32;
+  }
+  if min_hold > half_time {
+    return None;
+  }
+
+  let max_hold = time - &min_hold;
+  Some((min_hold, max_hold))
+}
+
+// Once at top speed the distance max_speed * (time - h) only shrinks, so it beats the record
+// while h < time - record / max_speed.
+fn find_capped_hold_times(race: &Race, capped_from: BigUint) -> Option<(BigUint, BigUint)> {
+  let max_speed = BigUint::from(race.boat.max_speed?);
+  if max_speed.is_zero() {
+    return None;
+  }
+  let losing_tail = &race.record / max_speed + 1u32;
+  if race.time < losing_tail {
+    return None;
+  }
+  let last_winning = &race.time - losing_tail;
+  if last_winning < capped_from {
+    return None;
+  }
+
+  Some((capped_from, last_winning))
+}
+
+// The distance rises until the boat hits top speed or half the race time, then falls, so the
+// winning hold times always form a single window.
+fn find_min_max_hold_times(race: &Race) -> Option<(BigUint, BigUint)> {
+  let capped_from = race.boat.capped_from().map(BigUint::from);
+  let uncapped = find_uncapped_hold_times(race).and_then(|(min_hold, max_hold)| match &capped_from {
+    Some(capped_from) if min_hold >= *capped_from => None,
+    Some(capped_from) => Some((min_hold, max_hold.min(capped_from - 1u32))),
+    None => Some((min_hold, max_hold))
+  });
+  let capped = capped_from.and_then(|capped_from| find_capped_hold_times(race, capped_from));
+  match (uncapped, capped) {
+    (Some(u), Some(c)) => Some((u.0.min(c.0), u.1.max(c.1))),
+    (u, c) => u.or(c)
+  }
+}
+
+fn find_best_hold_time(race: &Race) -> (BigUint, BigUint) {
+  let time = &race.time;
+  let mut candidates = vec![time / 2u32, (time + 1u32) / 2u32];
+  if let (Some(max_speed), true) = (race.boat.max_speed, race.boat.acceleration > 0) {
+    candidates.push(BigUint::from(max_speed / race.boat.acceleration));
+    candidates.push(BigUint::from(max_speed.div_ceil(race.boat.acceleration)));
+  }
+  let mut best = (BigUint::zero(), race.distance(&BigUint::zero()));
+  for hold in candidates.into_iter().filter(|hold| hold <= time) {
+    let distance = race.distance(&hold);
+    if distance > best.1 || (distance == best.1 && hold < best.0) {
+      best = (hold, distance);
+    }
+  }
+
+  best
+}
 
-        return races;
-      },
-      None => panic!("Could not parse races")
+fn find_strategy(race: &Race) -> RaceStrategy {
+  let (best_hold, best_distance) = find_best_hold_time(race);
+  RaceStrategy {
+    hold_times: find_min_max_hold_times(race),
+    best_hold,
+    best_distance
   }
 }
 
-fn find_min_max_hold_times(race: &Race) -> (usize, usize) {
-  let a: f32 = (race.time.pow(2) - 4 * race.record) as f32;
-  if a < 0.0 {
-    panic!("No solution for race {} {}", race.time, race.record);
+fn count_winning_hold_times(race: &Race) -> BigUint {
+  find_strategy(race).ways_to_win()
+}
+
+fn format_hold_times(hold_times: &Option<(BigUint, BigUint)>) -> String {
+  match hold_times {
+    Some((min_hold, max_hold)) => format!("{}..={}", min_hold, max_hold),
+    None => "none".to_owned()
   }
-  let root: f32 = a.sqrt();
-  let first_solution = ((race.time as f32 * -1.0) + root) / (-2.0);
-  let second_solution = ((race.time as f32 * -1.0) - root) / (-2.0);
-  let first_solution_rounded = if first_solution == first_solution.ceil() { first_solution + 1.0 } else { first_solution.ceil() };
-  let second_solution_rounded = if second_solution == second_solution.floor() { second_solution - 1.0 } else { second_solution.floor() };
+}
 
-  (first_solution_rounded as usize, second_solution_rounded as usize)
+fn print_report(races: &[Race]) {
+  println!("{:>4} {:>16} {:>20} {:>16} {:>16} {:>16} {:>16} {:>20} {:>20}", "Race", "Time", "Record", "Min hold", "Max hold", "Ways to win", "Best hold", "Best distance", "Margin");
+  for (idx, race) in races.iter().enumerate() {
+    let strategy = find_strategy(race);
+    let (min_hold, max_hold) = match &strategy.hold_times {
+      Some((min_hold, max_hold)) => (min_hold.to_string(), max_hold.to_string()),
+      None => ("-".to_owned(), "-".to_owned())
+    };
+    let margin = BigInt::from(strategy.best_distance.clone()) - BigInt::from(race.record.clone());
+    println!("{:>4} {:>16} {:>20} {:>16} {:>16} {:>16} {:>16} {:>20} {:>20}", idx + 1, race.time, race.record, min_hold, max_hold, strategy.ways_to_win(), strategy.best_hold, strategy.best_distance, margin);
+  }
 }
 
-fn prob_a(races: &Vec<Race>) -> usize {
-  let mut total: usize = 1;
+fn prob_a(races: &Vec<Race>) -> BigUint {
+  let mut total = BigUint::one();
   for race in races {
-    let hold_times = find_min_max_hold_times(race);
-    println!("Hold times {:?}", hold_times);
-    total = total * (hold_times.1 - hold_times.0 + 1);
+    println!("Hold times {}", format_hold_times(&find_min_max_hold_times(race)));
+    total *= count_winning_hold_times(race);
     println!("Total {}", total);
   }
 
   total
 }
 
-fn prob_b(races: &Vec<Race>) -> usize {
-  let mut time_str: String = "".to_owned();
-  let mut distance_str: String = "".to_owned();
-  for race in races {
-    time_str.push_str(race.time.to_string().as_str());
-    distance_str.push_str(race.record.to_string().as_str());
+fn prob_b(race: &Race) -> BigUint {
+  println!("Actual race: {} {}", race.time, race.record);
+  println!("Hold times {}", format_hold_times(&find_min_max_hold_times(race)));
+  count_winning_hold_times(race)
+}
+
+fn parse_boat(args: &[String]) -> Result<Boat, String> {
+  let mut boat = Boat::default();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    let value = match arg.as_str() {
+      "--acceleration" | "--max-speed" => iter.next().ok_or(format!("{} needs a number", arg))?,
+      _ => continue
+    };
+    let value = value.parse::<usize>().map_err(|_| format!("Invalid {} {}", arg, value))?;
+    if arg == "--acceleration" {
+      boat.acceleration = value;
+    } else {
+      boat.max_speed = Some(value);
+    }
   }
-  let actual_race = Race {
-    time: time_str.parse::<usize>().unwrap(),
-    record: distance_str.parse::<usize>().unwrap()
+
+  Ok(boat)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day6 <file> [a|b|report|report-b] [--acceleration N] [--max-speed N]")?;
+  let binding = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+  let file: &str = binding.as_str();
+
+  // Day6 <file> [a|b|report|report-b] [--acceleration N] [--max-speed N]
+  let boat = parse_boat(&args[2..])?;
+  let result = match args.get(2).map(|s| s.as_str()) {
+    Some("a") => prob_a(&parse_races(file, boat)?),
+    Some("b") | None => prob_b(&parse_single_race(file, boat)?),
+    Some("report") => {
+      print_report(&parse_races(file, boat)?);
+      return Ok(());
+    },
+    Some("report-b") => {
+      print_report(&[parse_single_race(file, boat)?]);
+      return Ok(());
+    },
+    Some(mode) if mode.starts_with("--") => prob_b(&parse_single_race(file, boat)?),
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
   };
-  println!("Actual race: {} {}", actual_race.time, actual_race.record);
-  let hold_times = find_min_max_hold_times(&actual_race);
-  println!("Hold times {:?}", hold_times);
-  hold_times.1 - hold_times.0 - 1
+
+  println!("Result: {}", result);
+  Ok(())
 }
 
 fn main() {
   let args: Vec<String> = std::env::args().collect();
-  let binding = fs::read_to_string(&args[1]).unwrap();
-  let file: &str = binding.as_str();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
 
-  let races: Vec<Race> = parse_input(file);
-  let result = prob_b(&races);
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use num::ToPrimitive;
 
-  println!("Result: {}", result);
-}
\ No newline at end of file
+  const MAX_TIME: usize = 30;
+
+  fn count_winning_hold_times_brute_force(race: &Race) -> BigUint {
+    let time = race.time.to_usize().unwrap();
+    let count = (0..=time)
+      .filter(|hold| race.distance(&BigUint::from(*hold)) > race.record)
+      .count();
+
+    BigUint::from(count)
+  }
+
+  // Every race up to MAX_TIME and every record up to one past the best reachable distance,
+  // across a few boats.
+  fn for_each_race(mut f: impl FnMut(&Race)) {
+    let max_speeds = [None, Some(0), Some(1), Some(2), Some(5), Some(10), Some(25)];
+    for acceleration in 0..=3 {
+      for max_speed in max_speeds {
+        let boat = Boat { acceleration, max_speed };
+        for time in 0..=MAX_TIME {
+          for record in 0..=(acceleration * time * time / 4 + 1) {
+            f(&Race { time: BigUint::from(time), record: BigUint::from(record), boat });
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn winning_hold_times_match_brute_force() {
+    for_each_race(|race| {
+      assert_eq!(count_winning_hold_times(race), count_winning_hold_times_brute_force(race),
+        "race {} {} with {:?}", race.time, race.record, race.boat);
+    });
+  }
+
+  #[test]
+  fn best_distance_matches_brute_force() {
+    for_each_race(|race| {
+      let strategy = find_strategy(race);
+      let time = race.time.to_usize().unwrap();
+      let best_distance = (0..=time).map(|hold| race.distance(&BigUint::from(hold))).max().unwrap();
+      assert_eq!(strategy.best_distance, best_distance, "race {} {} with {:?}", race.time, race.record, race.boat);
+      assert_eq!(race.distance(&strategy.best_hold), best_distance, "race {} {} with {:?}", race.time, race.record, race.boat);
+    });
+  }
+}
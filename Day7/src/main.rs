@@ -1,6 +1,13 @@
 use std::fs;
+use std::fmt;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::time::Instant;
+
+// Hand type ranks and tie breakers are packed 4 bits each into a u128 sort key
+const MAX_HAND_TYPES: usize = 16;
+const MAX_HAND_SIZE: usize = 31;
+const MAX_CARD_LABELS: usize = 15;
 
 #[derive(Debug, Clone)]
 struct Card {
@@ -9,33 +16,10 @@ struct Card {
 }
 
 impl Card {
-  pub fn new(label: char, j_as_joker: bool) -> Self {
-    let value: u8;
-    match label.to_digit(10) {
-      Some(n) => { value = n as u8 },
-      None => {
-        if label == 'T' {
-          value = 10;
-        } else if label == 'J' {
-          if j_as_joker {
-            value = 1;
-          } else {
-            value = 11;
-          }
-        } else if label == 'Q' {
-          value = 12;
-        } else if label == 'K' {
-          value = 13;
-        } else if label == 'A' {
-          value = 14;
-        } else {
-          panic!("Unexpected card {}!", label);
-        }
-      }
-    }
-    Self {
-      label: label,
-      value: value
+  pub fn new(label: char, rules: &dyn Rules) -> Result<Self, String> {
+    match rules.card_value(label) {
+      Some(value) => Ok(Self { label, value }),
+      None => Err(format!("Unexpected card {}", label))
     }
   }
 }
@@ -44,22 +28,6 @@ impl PartialOrd for Card {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
       Some(self.cmp(other))
   }
-
-  fn lt(&self, other: &Self) -> bool {
-    self.value < other.value
-  }
-
-  fn le(&self, other: &Self) -> bool {
-    self.value <= other.value
-  }
-
-  fn gt(&self, other: &Self) -> bool {
-    self.value > other.value
-  }
-
-  fn ge(&self, other: &Self) -> bool {
-    self.value >= other.value
-  }
 }
 
 impl Ord for Card {
@@ -75,63 +43,304 @@ impl PartialEq for Card {
 }
 impl Eq for Card {}
 
-#[derive(Debug)]
-struct Hand {
-  cards: Vec<Card>,
-  bid: usize,
-  j_as_joker: bool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+  HighCard,
+  OnePair,
+  TwoPair,
+  ThreeOfAKind,
+  FullHouse,
+  FourOfAKind,
+  FiveOfAKind
 }
 
-impl Hand {
-  fn get_type(&self) -> u8 {
-    let mut groups: HashMap<char, u8> = HashMap::new();
-    let cards = self.cards.clone();
-    let mut jokers = 0;
-    for card in &cards {
-      if self.j_as_joker && card.label == 'J' {
-        jokers = jokers + 1;
-      } else {
-        if groups.contains_key(&card.label) {
-          match groups.get(&card.label) {
-            Some(x) => { groups.insert(card.label, x + 1); },
-            None => {}
-          };
-        } else {
-          groups.insert(card.label, 1);
-        }
+impl HandType {
+  const ALL: [HandType; 7] = [
+    HandType::HighCard, HandType::OnePair, HandType::TwoPair, HandType::ThreeOfAKind,
+    HandType::FullHouse, HandType::FourOfAKind, HandType::FiveOfAKind
+  ];
+
+  // Smallest group sizes a hand needs to be of this type
+  fn groups(&self) -> Vec<u8> {
+    match self {
+      HandType::HighCard => vec![1],
+      HandType::OnePair => vec![2],
+      HandType::TwoPair => vec![2, 2],
+      HandType::ThreeOfAKind => vec![3],
+      HandType::FullHouse => vec![3, 2],
+      HandType::FourOfAKind => vec![4],
+      HandType::FiveOfAKind => vec![5]
+    }
+  }
+}
+
+impl fmt::Display for HandType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      HandType::HighCard => "High card",
+      HandType::OnePair => "One pair",
+      HandType::TwoPair => "Two pair",
+      HandType::ThreeOfAKind => "Three of a kind",
+      HandType::FullHouse => "Full house",
+      HandType::FourOfAKind => "Four of a kind",
+      HandType::FiveOfAKind => "Five of a kind"
+    };
+    f.pad(name)
+  }
+}
+
+// A hand type as ranked by a TypeTable, which may hold types beyond the standard HandTypes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TableType<'a> {
+  rank: usize,
+  name: &'a str
+}
+
+impl fmt::Display for TableType<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.pad(self.name)
+  }
+}
+
+// Hand types from weakest to strongest, each described by the smallest group sizes it needs:
+// a full house is a group of at least 3 plus another of at least 2. The weakest type is the
+// fallback for hands that match nothing else.
+struct TypeTable {
+  types: Vec<(String, Vec<u8>)>
+}
+
+impl TypeTable {
+  fn standard() -> Self {
+    Self {
+      types: HandType::ALL.iter().map(|hand_type| (hand_type.to_string(), hand_type.groups())).collect()
+    }
+  }
+
+  // One "<name>: <group sizes>" line per type, weakest first
+  fn parse(file: &str) -> Result<Self, String> {
+    let mut types = Vec::new();
+    for (idx, line) in file.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+      let (name, groups) = match line.split_once(':') {
+        Some(parts) => parts,
+        None => return Err(format!("Line {}: \"{}\" is not a \"<name>: <group sizes>\" hand type", idx + 1, line))
+      };
+      let mut groups = groups.split_whitespace()
+        .map(|n| n.parse::<u8>().map_err(|_| format!("Line {}: \"{}\" is not a group size", idx + 1, n)))
+        .collect::<Result<Vec<u8>, String>>()?;
+      // classify compares against the hand's groups, which are largest first
+      groups.sort_unstable_by(|a, b| b.cmp(a));
+      if groups.is_empty() {
+        return Err(format!("Line {}: hand type {} has no group sizes", idx + 1, name.trim()));
       }
+      types.push((name.trim().to_owned(), groups));
+    }
+    if types.is_empty() || types.len() > MAX_HAND_TYPES {
+      return Err(format!("Expected between 1 and {} hand types, found {}", MAX_HAND_TYPES, types.len()));
     }
-    let mut kinds: Vec<u8> = groups.into_values().collect();
-    kinds.sort();
-    kinds.reverse();
-    if kinds.len() < 1 {
-      if self.j_as_joker && jokers == 5 {
-        return 6 // Five of a kind
+
+    Ok(Self { types })
+  }
+
+  // Strongest type whose group sizes are all covered by the hand's groups, largest first
+  fn classify(&self, groups: &[u8]) -> TableType<'_> {
+    let rank = self.types.iter()
+      .rposition(|(_, needed)| {
+        needed.len() <= groups.len() && needed.iter().zip(groups.iter()).all(|(n, g)| g >= n)
+      })
+      .unwrap_or(0);
+
+    TableType { rank, name: &self.types[rank].0 }
+  }
+}
+
+// A Camel Cards variant: how cards rank, which of them are wild, how a hand is classified and
+// how hands of the same type are told apart.
+trait Rules {
+  // Card labels from weakest to strongest
+  fn card_order(&self) -> &str;
+
+  fn type_table(&self) -> &TypeTable;
+
+  fn card_value(&self, label: char) -> Option<u8> {
+    self.card_order().chars().position(|c| c == label).map(|idx| idx as u8 + 1)
+  }
+
+  fn is_wild(&self, _card: &Card) -> bool {
+    false
+  }
+
+  // Groups of identical non-wild cards, largest and strongest first, and the number of wild cards
+  fn group_cards(&self, cards: &[Card]) -> (Vec<(Card, u8)>, u8) {
+    let mut groups: HashMap<char, (Card, u8)> = HashMap::new();
+    let mut wilds = 0;
+    for card in cards {
+      if self.is_wild(card) {
+        wilds += 1;
       } else {
-        panic!("Hand has not enough kinds of cards to get a type");
+        groups.entry(card.label).or_insert((card.clone(), 0)).1 += 1;
       }
     }
-    if self.j_as_joker {
-      kinds[0] = kinds[0] + jokers;
+    let mut kinds: Vec<(Card, u8)> = groups.into_values().collect();
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+
+    (kinds, wilds)
+  }
+
+  // Tries every way of handing out the wild cards, each joining a group of the other cards
+  // or starting a new one, since a custom table need not reward the largest group most.
+  // Returns the strongest type with how many wilds joined each group and the sizes of the
+  // new groups.
+  fn best_wild_assignment(&self, cards: &[Card]) -> (TableType<'_>, Vec<u8>, Vec<u8>) {
+    let (groups, wilds) = self.group_cards(cards);
+    let counts: Vec<u8> = groups.iter().map(|(_, count)| *count).collect();
+    let mut best = None;
+    join_wilds(self.type_table(), &counts, &mut vec![0; counts.len()], 0, wilds, &mut best);
+
+    best.expect("Every hand has at least one wild card assignment")
+  }
+
+  fn hand_type(&self, cards: &[Card]) -> TableType<'_> {
+    self.best_wild_assignment(cards).0
+  }
+
+  // The labels the wild cards stand in for, if the hand has any. Wilds starting a new group
+  // take the strongest labels the hand does not hold.
+  fn wild_substitute(&self, cards: &[Card]) -> Option<String> {
+    let (groups, wilds) = self.group_cards(cards);
+    if wilds == 0 {
+      return None;
+    }
+    let (_, joined, new) = self.best_wild_assignment(cards);
+    let mut unused = self.card_order().chars().rev()
+      .filter(|label| groups.iter().all(|(card, _)| card.label != *label))
+      .filter(|label| self.card_value(*label).is_some_and(|value| !self.is_wild(&Card { label: *label, value })));
+    let mut labels = String::new();
+    for ((card, _), count) in groups.iter().zip(&joined) {
+      labels.extend(std::iter::repeat_n(card.label, *count as usize));
+    }
+    for count in new {
+      labels.extend(std::iter::repeat_n(unused.next().unwrap_or('?'), count as usize));
+    }
+
+    Some(labels)
+  }
+
+  // Values compared in order when two hands have the same type
+  fn tie_breakers(&self, cards: &[Card]) -> Vec<u8> {
+    cards.iter().map(|card| card.value).collect()
+  }
+
+  // Hand type rank in the top 4 bits followed by the tie breakers, 4 bits each, so that hands
+  // compare as plain integers. Hands are at most MAX_HAND_SIZE cards.
+  fn sort_key(&self, cards: &[Card]) -> u128 {
+    let tie_breakers = self.tie_breakers(cards);
+    let mut key = self.hand_type(cards).rank as u128;
+    for idx in 0..MAX_HAND_SIZE {
+      key = (key << 4) | *tie_breakers.get(idx).unwrap_or(&0) as u128;
+    }
+
+    key
+  }
+}
+
+type WildAssignment<'a> = (TableType<'a>, Vec<u8>, Vec<u8>);
+
+// Adds wilds to the groups from `idx` on (so each split is only tried once), then leaves the
+// rest to new groups. Joining is tried first, so it wins ties.
+fn join_wilds<'a>(table: &'a TypeTable, counts: &[u8], joined: &mut Vec<u8>, idx: usize, wilds: u8,
+  best: &mut Option<WildAssignment<'a>>) {
+  if wilds > 0 {
+    for i in idx..joined.len() {
+      joined[i] += 1;
+      join_wilds(table, counts, joined, i, wilds - 1, best);
+      joined[i] -= 1;
+    }
+  }
+  split_wilds(table, counts, joined, &mut Vec::new(), wilds, wilds, best);
+}
+
+// Splits the remaining wilds into new groups of non-increasing size and classifies the result
+fn split_wilds<'a>(table: &'a TypeTable, counts: &[u8], joined: &[u8], new: &mut Vec<u8>, wilds: u8, max_size: u8,
+  best: &mut Option<WildAssignment<'a>>) {
+  if wilds == 0 {
+    let mut kinds: Vec<u8> = counts.iter().zip(joined).map(|(count, extra)| count + extra)
+      .chain(new.iter().copied())
+      .collect();
+    kinds.sort_unstable_by(|a, b| b.cmp(a));
+    let hand_type = table.classify(&kinds);
+    if best.as_ref().is_none_or(|(best_type, _, _)| hand_type > *best_type) {
+      *best = Some((hand_type, joined.to_vec(), new.clone()));
     }
-    if kinds[0] == 5 {
-      6 // Five of a kind
-    } else if kinds[0] == 4 {
-      5 // Four of a kind
-    } else if kinds[0] == 3 && kinds[1] == 2 {
-      4 // Full house
-    } else if kinds[0] == 3 {
-      3 // Three of a kind
-    } else if kinds[0] == 2 && kinds[1] == 2 {
-      2 // Two pair
-    } else if kinds[0] == 2 {
-      1 // One pair
-    } else {
-      0 // High card
+    return;
+  }
+  for size in (1..=wilds.min(max_size)).rev() {
+    new.push(size);
+    split_wilds(table, counts, joined, new, wilds - size, size, best);
+    new.pop();
+  }
+}
+
+struct StandardRules {
+  types: TypeTable
+}
+
+impl Rules for StandardRules {
+  fn card_order(&self) -> &str {
+    "23456789TJQKA"
+  }
+
+  fn type_table(&self) -> &TypeTable {
+    &self.types
+  }
+}
+
+// Wild cards are the weakest individually but count as whatever makes the best hand
+struct JokerRules {
+  card_order: String,
+  wilds: Vec<char>,
+  types: TypeTable
+}
+
+impl JokerRules {
+  // Wild labels must be cards of the deck, each named once
+  fn new(wilds: &str, types: TypeTable) -> Result<Self, String> {
+    const DECK: &str = "23456789TJQKA";
+    for (idx, label) in wilds.char_indices() {
+      if !DECK.contains(label) {
+        return Err(format!("Wild card {} is not one of {}", label, DECK));
+      }
+      if wilds[..idx].contains(label) {
+        return Err(format!("Wild card {} is listed twice", label));
+      }
     }
+    let others: String = DECK.chars().filter(|c| !wilds.contains(*c)).collect();
+    Ok(Self {
+      card_order: format!("{}{}", wilds, others),
+      wilds: wilds.chars().collect(),
+      types
+    })
   }
 }
 
+impl Rules for JokerRules {
+  fn card_order(&self) -> &str {
+    &self.card_order
+  }
+
+  fn type_table(&self) -> &TypeTable {
+    &self.types
+  }
+
+  fn is_wild(&self, card: &Card) -> bool {
+    self.wilds.contains(&card.label)
+  }
+}
+
+struct Hand {
+  cards: Vec<Card>,
+  bid: usize,
+  key: u128
+}
 
 impl PartialOrd for Hand {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -141,69 +350,193 @@ impl PartialOrd for Hand {
 
 impl Ord for Hand {
   fn cmp(&self, other_hand: &Self) -> Ordering {
-    let own_type = self.get_type();
-    let other_type = other_hand.get_type();
-    if own_type > other_type {
-      return Ordering::Greater;
-    } else if own_type < other_type {
-      return Ordering::Less;
-    } else {
-      let mut idx = 0;
-      while idx < self.cards.len() {
-        if self.cards[idx] > other_hand.cards[idx] {
-          return Ordering::Greater;
-        } else if self.cards[idx] < other_hand.cards[idx] {
-          return Ordering::Less;
-        }
-        idx = idx + 1;
-      }
-    }
-    Ordering::Equal
+    self.key.cmp(&other_hand.key)
   }
 }
 impl PartialEq for Hand {
   fn eq(&self, other: &Self) -> bool {
-      self.cmp(other) == Ordering::Equal
+      self.key == other.key
   }
 }
 impl Eq for Hand {}
 
-fn parse_input(lines: Vec<&str>, j_as_joker: bool) -> Vec<Hand> {
+fn parse_hand(line: &str, rules: &dyn Rules) -> Result<Hand, String> {
+  let parts: Vec<&str> = line.split_whitespace().collect();
+  if parts.len() != 2 {
+    return Err(format!("Hand {} does not have two components as expected", line));
+  }
+  let cards = parts[0].chars()
+    .map(|c| Card::new(c, rules))
+    .collect::<Result<Vec<Card>, String>>()?;
+  if cards.len() > MAX_HAND_SIZE {
+    return Err(format!("Hand {} has more than {} cards", parts[0], MAX_HAND_SIZE));
+  }
+  let bid = match parts[1].parse::<usize>() {
+    Ok(bid) => bid,
+    Err(_) => return Err(format!("Bid {} is not a number", parts[1]))
+  };
+
+  Ok(Hand {
+    key: rules.sort_key(&cards),
+    cards,
+    bid
+  })
+}
+
+// All hands must have the same number of cards, whatever that number is
+fn parse_input(lines: Vec<&str>, rules: &dyn Rules) -> Result<Vec<Hand>, String> {
   let mut hands: Vec<Hand> = Vec::new();
-  for line in lines {
-    let parts: Vec<&str> = line.split(" ").collect();
-    if parts.len() != 2 {
-      panic!("Hand {} does not have two components as expected!", line);
+  for (idx, line) in lines.iter().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+    let hand = parse_hand(line, rules).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+    if let Some(first) = hands.first() {
+      if first.cards.len() != hand.cards.len() {
+        return Err(format!("Line {}: hand has {} cards, expected {}", idx + 1, hand.cards.len(), first.cards.len()));
+      }
     }
-    hands.push(Hand {
-      cards: parts[0].chars().map(|c| Card::new(c, j_as_joker)).collect(),
-      bid: parts[1].parse::<usize>().unwrap(),
-      j_as_joker: j_as_joker
-    });
+    hands.push(hand);
   }
 
-  hands
+  Ok(hands)
+}
+
+fn explain(hands: &[Hand], rules: &dyn Rules) {
+  println!("{:>6} {:>7} {:>16} {:>9} {:>6} {:>10}", "Rank", "Hand", "Type", "Wilds as", "Bid", "Winnings");
+  for (rank, hand) in (1..).zip(hands.iter()) {
+    let labels: String = hand.cards.iter().map(|card| card.label).collect();
+    let wilds_as = match rules.wild_substitute(&hand.cards) {
+      Some(labels) => labels,
+      None => "-".to_owned()
+    };
+    println!("{:>6} {:>7} {:>16} {:>9} {:>6} {:>10}", rank, labels, rules.hand_type(&hand.cards), wilds_as, hand.bid, hand.bid * rank);
+  }
 }
 
-fn solve(hands: &Vec<Hand>) -> usize {
+fn solve(hands: &[Hand]) -> usize {
   let mut total: usize = 0;
-  let mut rank: usize = 1;
-  for hand in hands {
-    total = total + hand.bid * rank;
-    rank = rank + 1;
+  for (rank, hand) in (1..).zip(hands.iter()) {
+    total += hand.bid * rank;
   }
   total
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  let file: String = fs::read_to_string(&args[1]).unwrap();
+fn generate_hands(count: usize, rules: &dyn Rules) -> Vec<String> {
+  let labels: Vec<char> = rules.card_order().chars().collect();
+  let mut seed: u64 = 0x2545F4914F6CDD1D;
+  let mut next = || {
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    seed
+  };
+  (0..count)
+    .map(|_| {
+      let cards: String = (0..5).map(|_| labels[next() as usize % labels.len()]).collect();
+      format!("{} {}", cards, next() % 1000 + 1)
+    })
+    .collect()
+}
+
+// Sort generated hands by their precomputed keys, and by re-classifying both hands on every
+// comparison as a baseline.
+fn bench(count: usize, rules: &dyn Rules) -> Result<(), String> {
+  let generated = generate_hands(count, rules);
+  let lines: Vec<&str> = generated.iter().map(|s| s.as_str()).collect();
+
+  let start = Instant::now();
+  let mut hands = parse_input(lines.clone(), rules)?;
+  println!("Time elapsed parsing {} hands is: {:?}", count, start.elapsed());
+  let start = Instant::now();
+  hands.sort();
+  println!("Time elapsed sorting by key is: {:?}", start.elapsed());
+  let keyed_result = solve(&hands);
+
+  let mut hands = parse_input(lines, rules)?;
+  let start = Instant::now();
+  hands.sort_by(|a, b| {
+    rules.hand_type(&a.cards).cmp(&rules.hand_type(&b.cards))
+      .then_with(|| rules.tie_breakers(&a.cards).cmp(&rules.tie_breakers(&b.cards)))
+  });
+  println!("Time elapsed sorting by re-classifying is: {:?}", start.elapsed());
+  if solve(&hands) != keyed_result {
+    return Err("Sorting by key and by re-classifying disagree".to_owned());
+  }
+
+  println!("Result: {}", keyed_result);
+  Ok(())
+}
+
+struct Options {
+  positional: Vec<String>,
+  explain: bool,
+  types: TypeTable
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+  let mut options = Options { positional: Vec::new(), explain: false, types: TypeTable::standard() };
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--explain" => options.explain = true,
+      "--types" => {
+        let path = iter.next().ok_or("--types needs a file")?;
+        let file = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        options.types = TypeTable::parse(&file).map_err(|e| format!("{}: {}", path, e))?;
+      },
+      _ => options.positional.push(arg.clone())
+    }
+  }
+
+  Ok(options)
+}
+
+fn parse_rules(args: &[String], types: TypeTable) -> Result<Box<dyn Rules>, String> {
+  let rules: Box<dyn Rules> = match args.first().map(|s| s.as_str()) {
+    Some("standard") => Box::new(StandardRules { types }),
+    Some("joker") | None => Box::new(JokerRules::new(args.get(1).map(|s| s.as_str()).unwrap_or("J"), types)?),
+    Some(rules) => return Err(format!("Unknown rules {}", rules))
+  };
+  // Card values start at 1 and must fit their 4 bit slot in the sort key
+  if rules.card_order().chars().count() > MAX_CARD_LABELS {
+    return Err(format!("The card order {} has more than {} labels", rules.card_order(), MAX_CARD_LABELS));
+  }
+
+  Ok(rules)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day7 <file>|bench [count] [standard|joker [wild labels]] [--types file] [--explain]")?;
+  // Day7 bench [count] [standard|joker [wild labels]] [--types file]
+  if path == "bench" {
+    let options = parse_options(&args[2..])?;
+    let count = match options.positional.first() {
+      Some(n) => n.parse::<usize>().map_err(|_| format!("Invalid hand count {}", n))?,
+      None => 1_000_000
+    };
+    let rules = parse_rules(options.positional.get(1..).unwrap_or(&[]), options.types)?;
+    return bench(count, rules.as_ref());
+  }
+  let file: String = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
   let lines: Vec<&str> = file.lines()
     .collect();
 
-  let mut hands = parse_input(lines, true);
+  // Day7 <file> [standard|joker [wild labels]] [--types file] [--explain]
+  let options = parse_options(&args[2..])?;
+  let rules = parse_rules(&options.positional, options.types)?;
+  let mut hands = parse_input(lines, rules.as_ref())?;
   hands.sort();
+  if options.explain {
+    explain(&hands, rules.as_ref());
+  }
   let result = solve(&hands);
 
   println!("Result: {}", result);
-}
\ No newline at end of file
+  Ok(())
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
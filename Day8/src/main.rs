@@ -1,208 +1,533 @@
 use std::fs;
+use std::fmt::Write;
 use regex::Regex;
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 use once_cell::sync::Lazy;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use num::integer::lcm;
+use rayon::prelude::*;
+use num::integer::{lcm, ExtendedGcd, Integer};
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Direction {
-  Left,
-  Right
+  Left = 0,
+  Right = 1
 }
 
-struct Node {
-  id: String,
-  left: String,
-  right: String
+// Node names are interned to dense u32 ids, and edges[id] holds the left and right neighbours
+struct Network {
+  names: Vec<String>,
+  ids: HashMap<String, u32>,
+  edges: Vec<[u32; 2]>
 }
 
-impl Node {
-  pub fn new(input: &str) -> Self {
-    static NODE_RE: Lazy<regex::Regex> = Lazy::new(|| Regex::new(r"(?<id>\w{3}) = \((?<left>\w{3}), (?<right>\w{3})\)").unwrap());
-    match NODE_RE.captures(input) {
-      Some(captures) => {
-        Node {
-          id: (&captures["id"]).to_owned(),
-          left: (&captures["left"]).to_owned(),
-          right: (&captures["right"]).to_owned(),
+impl Network {
+  fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  fn id(&self, name: &str) -> Option<u32> {
+    self.ids.get(name).copied()
+  }
+
+  fn name(&self, id: u32) -> &str {
+    &self.names[id as usize]
+  }
+
+  fn step(&self, node: u32, direction: Direction) -> u32 {
+    self.edges[node as usize][direction as usize]
+  }
+
+  fn find_nodes(&self, predicate: impl Fn(&str) -> bool) -> Vec<u32> {
+    (0..self.len() as u32)
+      .filter(|id| predicate(self.name(*id)))
+      .collect()
+  }
+}
+
+// Where a node ends up after one full pass over the directions, and at which offsets into the
+// pass the walk stands on an end node (offset 0 being the node itself). Hit offsets of node n
+// are hit_offsets[hit_starts[n]..hit_starts[n + 1]].
+struct PassTable {
+  pass_len: usize,
+  end: Vec<u32>,
+  hit_starts: Vec<usize>,
+  hit_offsets: Vec<u32>
+}
+
+impl PassTable {
+  fn new(network: &Network, directions: &[Direction], is_end: &[bool]) -> Self {
+    let passes: Vec<(u32, Vec<u32>)> = (0..network.len() as u32).into_par_iter()
+      .map(|start| {
+        let mut node = start;
+        let mut hits = Vec::new();
+        for (offset, direction) in directions.iter().enumerate() {
+          if is_end[node as usize] {
+            hits.push(offset as u32);
+          }
+          node = network.step(node, *direction);
         }
-      },
-      None => panic!("Could not parse node {}", input)
+        (node, hits)
+      })
+      .collect();
+    let mut end = Vec::with_capacity(passes.len());
+    let mut hit_starts = Vec::with_capacity(passes.len() + 1);
+    let mut hit_offsets = Vec::new();
+    for (node, hits) in passes {
+      end.push(node);
+      hit_starts.push(hit_offsets.len());
+      hit_offsets.extend(hits);
     }
+    hit_starts.push(hit_offsets.len());
+
+    PassTable { pass_len: directions.len(), end, hit_starts, hit_offsets }
+  }
+
+  fn hits(&self, node: u32) -> &[u32] {
+    &self.hit_offsets[self.hit_starts[node as usize]..self.hit_starts[node as usize + 1]]
   }
 }
 
-fn parse_input(file: &str) -> (Vec<Direction>, HashMap<String, Node>) {
-  let start = Instant::now();
-  let re = Regex::new(r"(?s)(?<directions>[LR]+)\n\n(?<nodes>.+)").unwrap();
-  match re.captures(file) {
-      Some(captures) => {
-        let directions: Vec<Direction> = (&captures["directions"])
-          .chars()
-          .map(|d| if d == 'L' { Direction::Left } else { Direction::Right }).collect();
-        let node_list = (&captures["nodes"])
-          .split("\n");
-        let mut node_map: HashMap<String, Node> = HashMap::new();
-        for node_def in node_list {
-          let start_n = Instant::now();
-          let new_node = Node::new(node_def);
-          node_map.insert(new_node.id.clone(), new_node);
-        }
+fn parse_input(file: &str) -> (Vec<Direction>, Network) {
+  static NODE_RE: Lazy<regex::Regex> = Lazy::new(|| Regex::new(r"^(?<id>\w+) = \((?<left>\w+), (?<right>\w+)\)$").unwrap());
+  let mut lines = file.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+  let directions: Vec<Direction> = match lines.next() {
+    Some(line) if !line.is_empty() && line.chars().all(|d| d == 'L' || d == 'R') => line
+      .chars()
+      .map(|d| if d == 'L' { Direction::Left } else { Direction::Right }).collect(),
+    _ => panic!("Could not parse input file!")
+  };
 
-        return (directions, node_map);
-      },
-      None => panic!("Could not parse input file!")
+  let mut definitions: Vec<(&str, &str, &str)> = Vec::new();
+  for node_def in lines {
+    match NODE_RE.captures(node_def) {
+      Some(captures) => definitions.push((
+        captures.name("id").unwrap().as_str(),
+        captures.name("left").unwrap().as_str(),
+        captures.name("right").unwrap().as_str()
+      )),
+      None => panic!("Could not parse node {}", node_def)
+    }
+  }
+  let names: Vec<String> = definitions.iter().map(|(id, _, _)| (*id).to_owned()).collect();
+  let ids: HashMap<String, u32> = names.iter().enumerate().map(|(idx, name)| (name.clone(), idx as u32)).collect();
+  let lookup = |name: &str| match ids.get(name) {
+    Some(id) => *id,
+    None => panic!("Could not find node {}", name)
+  };
+  let edges: Vec<[u32; 2]> = definitions.iter()
+    .map(|(_, left, right)| [lookup(left), lookup(right)])
+    .collect();
+
+  (directions, Network { names, ids, edges })
+}
+
+// Picks nodes by name: exact:AAA, prefix:1, suffix:Z or regex:^[0-9]+Z$
+enum Selector {
+  Exact(String),
+  Prefix(String),
+  Suffix(String),
+  Pattern(Regex)
+}
+
+impl Selector {
+  fn parse(spec: &str) -> Result<Self, String> {
+    match spec.split_once(':') {
+      Some(("exact", name)) => Ok(Selector::Exact(name.to_owned())),
+      Some(("prefix", prefix)) => Ok(Selector::Prefix(prefix.to_owned())),
+      Some(("suffix", suffix)) => Ok(Selector::Suffix(suffix.to_owned())),
+      Some(("regex", pattern)) => Regex::new(pattern)
+        .map(Selector::Pattern)
+        .map_err(|e| format!("Invalid regex {}: {}", pattern, e)),
+      _ => Err(format!("Invalid node selector {}, expected exact:, prefix:, suffix: or regex:", spec))
+    }
+  }
+
+  fn matches(&self, name: &str) -> bool {
+    match self {
+      Selector::Exact(id) => name == id,
+      Selector::Prefix(prefix) => name.starts_with(prefix.as_str()),
+      Selector::Suffix(suffix) => name.ends_with(suffix.as_str()),
+      Selector::Pattern(re) => re.is_match(name)
+    }
+  }
+
+  fn find_nodes(&self, network: &Network) -> Vec<u32> {
+    match self {
+      Selector::Exact(id) => network.id(id).into_iter().collect(),
+      _ => network.find_nodes(|name| self.matches(name))
+    }
+  }
+
+  fn mark_nodes(&self, network: &Network) -> Vec<bool> {
+    (0..network.len() as u32).map(|id| self.matches(network.name(id))).collect()
   }
 }
 
-fn find_start_nodes(nodes: &HashMap<String, Node>) -> Vec<String> {
-  nodes.keys()
-    .filter(|key| key.ends_with("A"))
-    .map(|key| key.clone() )
+// Steps from each start node to its first end node, or None when it never gets to one
+fn prob_a(directions: &[Direction], network: &Network, start: &Selector, end: &Selector) -> Vec<(u32, Option<usize>)> {
+  let table = PassTable::new(network, directions, &end.mark_nodes(network));
+  start.find_nodes(network).into_par_iter()
+    .map(|n| {
+      let walk = walk_until_cycle(n, &table);
+      (n, walk.prefix_hits.first().or(walk.cycle_hits.first()).copied())
+    })
     .collect()
 }
 
-fn all_nodes_have_same_distances(current_nodes: &Vec<String>, distances: &HashMap<String, (usize, String)>) -> bool {
-  let distance: usize;
-  match distances.get(&current_nodes[0]) {
-    Some(d) => {
-      if d.0 == usize::MAX {
-        return false;
-      }
-      distance = d.0.clone();
-    },
-    None => panic!("Could not find distance {}", current_nodes[0])
+// Moves every ghost one step at a time until they all stand on end nodes, giving up after
+// limit steps. Only practical for small networks, where it serves as an oracle for prob_b_2.
+fn prob_b(directions: &[Direction], network: &Network, start: &Selector, end: &Selector, limit: usize) -> Option<usize> {
+  let is_end = end.mark_nodes(network);
+  let mut current_nodes: Vec<u32> = start.find_nodes(network);
+  // Without ghosts there is no arrival, as for find_simultaneous_arrival
+  if current_nodes.is_empty() {
+    return None;
   }
-  let mut idx = 1;
-  while idx < current_nodes.len() {
-    match distances.get(&current_nodes[idx]) {
-      Some(d) => if d.0 == usize::MAX || d.0 != distance { return false },
-      None => panic!("Could not find distance {}", current_nodes[idx])
+  for step in 0..=limit {
+    if current_nodes.iter().all(|n| is_end[*n as usize]) {
+      return Some(step);
+    }
+    let direction = directions[step % directions.len()];
+    for node in current_nodes.iter_mut() {
+      *node = network.step(*node, direction);
     }
-    idx = idx + 1;
   }
 
-  true
+  None
 }
 
-fn find_distances_to_end(distances: &mut HashMap<String, (usize, String)>, nodes: &HashMap<String, Node>, directions: &Vec<Direction>) {
-  let keys = distances.keys().clone();
-  for (key, value) in distances.iter_mut() {
-    let mut distance = 0;
-    let mut current_node: String = key.clone();
-    for direction in directions {
-      match nodes.get(&current_node) {
-        Some(n) => current_node = if *direction == Direction::Left { n.left.clone() } else { n.right.clone() },
-        None => panic!("Could not find node {}", current_node)
-      }
-      distance = distance + 1;
-      if current_node.ends_with("Z") {
-        value.0 = distance;
-      }
+// Everything a walk from one start node does before it starts repeating itself. The walk is
+// in a cycle once it is back at a node it already left with the same direction index, so
+// from then on it hits end nodes at cycle_hits + k * cycle_len.
+struct Walk {
+  prefix_hits: Vec<usize>,
+  cycle_start: usize,
+  cycle_len: usize,
+  cycle_hits: Vec<usize>
+}
+
+impl Walk {
+  fn hits_at(&self, step: usize) -> bool {
+    if step < self.cycle_start {
+      return self.prefix_hits.contains(&step);
     }
-    value.1 = current_node;
+    self.cycle_hits.iter().any(|hit| step >= *hit && (step - hit).is_multiple_of(self.cycle_len))
   }
+
+  // The classic shortcut only holds when the first end node is reached exactly one cycle in
+  // and it is the only end node of the cycle.
+  fn lcm_period(&self) -> Option<usize> {
+    match (self.prefix_hits.as_slice(), self.cycle_hits.as_slice()) {
+      ([], [hit]) if *hit == self.cycle_len => Some(self.cycle_len),
+      _ => None
+    }
+  }
+}
+
+// Jumps a full pass of directions at a time, so the walk is only ever compared at direction
+// index 0. Any cycle of (node, direction index) states spans whole passes, so this finds the
+// same cycle length, possibly entered up to one pass later. The pass at which each node was
+// seen is kept per walk, so memory follows the walk's length rather than the network's size.
+fn walk_until_cycle(start: u32, table: &PassTable) -> Walk {
+  let mut seen: HashMap<u32, u32> = HashMap::new();
+  let mut hits: Vec<usize> = Vec::new();
+  let mut current_node = start;
+  let mut pass = 0;
+  let first_pass = loop {
+    if let Some(first_pass) = seen.get(&current_node) {
+      break *first_pass;
+    }
+    seen.insert(current_node, pass);
+    hits.extend(table.hits(current_node).iter().map(|offset| pass as usize * table.pass_len + *offset as usize));
+    current_node = table.end[current_node as usize];
+    pass += 1;
+  };
+
+  let cycle_start = first_pass as usize * table.pass_len;
+  let (prefix_hits, cycle_hits) = hits.into_iter().partition(|hit| *hit < cycle_start);
+  Walk {
+    prefix_hits,
+    cycle_start,
+    cycle_len: pass as usize * table.pass_len - cycle_start,
+    cycle_hits
+  }
+}
+
+// Smallest x >= 0 with x = r1 (mod m1) and x = r2 (mod m2), and the combined modulus
+fn crt(r1: i128, m1: i128, r2: i128, m2: i128) -> Option<(i128, i128)> {
+  let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+  if (r2 - r1) % gcd != 0 {
+    return None;
+  }
+  let modulus = m1 / gcd * m2;
+  let k = ((r2 - r1) / gcd % (m2 / gcd)) * x % (m2 / gcd);
+  Some(((r1 + m1 * k).rem_euclid(modulus), modulus))
 }
 
-fn do_iteration(current_nodes: &mut Vec<String>, nodes: &HashMap<String, Node>, directions: &Vec<Direction>) -> usize {
-  let mut idx = 0;
-  while idx < current_nodes.len() {
-    let mut current_node: String = current_nodes[idx].clone();
+// First step at which every walk is on an end node at the same time, and how it was found
+fn find_simultaneous_arrival(walks: &[Walk]) -> (Option<u128>, &'static str) {
+  let latest = match walks.iter().max_by_key(|w| w.cycle_start) {
+    Some(latest) => latest,
+    None => return (None, "no walks")
+  };
+  // Before the last walk enters its cycle, only its own prefix hits are candidates
+  if let Some(step) = latest.prefix_hits.iter().find(|hit| walks.iter().all(|w| w.hits_at(**hit))) {
+    return (Some(*step as u128), "before the cycles");
+  }
+
+  if let Some(periods) = walks.iter().map(|w| w.lcm_period()).collect::<Option<Vec<usize>>>() {
+    return (Some(periods.into_iter().fold(1u128, |acc, p| lcm(acc, p as u128))), "the LCM of the cycle lengths");
+  }
+
+  let mut solutions: Vec<(i128, i128)> = vec![(0, 1)];
+  for walk in walks {
+    solutions = solutions.iter()
+      .flat_map(|(r, m)| walk.cycle_hits.iter().filter_map(move |hit| crt(*r, *m, *hit as i128, walk.cycle_len as i128)))
+      .collect();
+    solutions.sort();
+    solutions.dedup();
+  }
+  let min_step = latest.cycle_start as i128;
+  let step = solutions.into_iter()
+    .map(|(r, m)| if r >= min_step { r } else { r + (min_step - r + m - 1) / m * m })
+    .min()
+    .map(|step| step as u128);
+
+  (step, "the Chinese Remainder Theorem")
+}
+
+fn find_walks(directions: &[Direction], network: &Network, start: &Selector, end: &Selector) -> Vec<Walk> {
+  let table = PassTable::new(network, directions, &end.mark_nodes(network));
+  start.find_nodes(network).par_iter()
+    .map(|n| walk_until_cycle(*n, &table))
+    .collect()
+}
+
+fn prob_b_2(directions: &[Direction], network: &Network, start: &Selector, end: &Selector) -> Option<u128> {
+  let start_time = Instant::now();
+  let walks = find_walks(directions, network, start, end);
+  println!("Time elapsed finding the cycles: {:?}", start_time.elapsed());
+  let (result, method) = find_simultaneous_arrival(&walks);
+  println!("Solved using {}", method);
+
+  result
+}
+
+// First step at which the walk from start stands on each node it ever reaches. The walk stops
+// once a pass starts from a node that already started an earlier pass, as nothing new can
+// be reached from there.
+fn find_first_visits(start: u32, network: &Network, directions: &[Direction]) -> HashMap<u32, usize> {
+  let mut pass_started: HashSet<u32> = HashSet::new();
+  let mut first_visits: HashMap<u32, usize> = HashMap::new();
+  let mut current_node = start;
+  let mut steps = 0;
+  while pass_started.insert(current_node) {
     for direction in directions {
-      match nodes.get(&current_node) {
-        Some(n) => current_node = if *direction == Direction::Left { n.left.clone() } else { n.right.clone() },
-        None => panic!("Could not find node {}", current_node)
+      first_visits.entry(current_node).or_insert(steps);
+      current_node = network.step(current_node, *direction);
+      steps += 1;
+    }
+  }
+
+  first_visits
+}
+
+// Steps from every node matching from to every node matching to
+fn query(directions: &[Direction], network: &Network, from: &Selector, to: &Selector) -> Result<(), String> {
+  let sources = from.find_nodes(network);
+  let targets = to.find_nodes(network);
+  if sources.is_empty() || targets.is_empty() {
+    return Err("The query selectors do not match any node".to_owned());
+  }
+  for source in sources {
+    let first_visits = find_first_visits(source, network, directions);
+    for target in &targets {
+      match first_visits.get(target) {
+        Some(steps) => println!("{} -> {}: {} steps", network.name(source), network.name(*target), steps),
+        None => println!("{} -> {}: unreachable", network.name(source), network.name(*target))
       }
     }
-    current_nodes[idx] = current_node;
-    idx = idx + 1;
   }
 
-  directions.len()
+  Ok(())
 }
 
-fn calc_cycle(node: &String, nodes: &HashMap<String, Node>, directions: &Vec<Direction>) -> usize {
-  let mut distance = 0;
-  let mut current_node: String = node.clone();
+// Edges taken walking from start until an end node is reached or the walk starts repeating
+fn find_path(start: u32, network: &Network, directions: &[Direction], is_end: &[bool]) -> HashSet<(u32, Direction)> {
+  let mut seen: HashSet<(u32, usize)> = HashSet::new();
+  let mut path: HashSet<(u32, Direction)> = HashSet::new();
+  let mut current_node = start;
   let mut direction_idx = 0;
-  while true {
-    match nodes.get(&current_node) {
-      Some(n) => current_node = if directions[direction_idx] == Direction::Left { n.left.clone() } else { n.right.clone() },
-      None => panic!("Could not find node {}", current_node)
-    }
-    distance = distance + 1;
-    if current_node.ends_with("Z") {
-      return distance;
-    }
-    direction_idx = direction_idx + 1;
-    if direction_idx >= directions.len() {
-      direction_idx = 0;
-    }
+  while !is_end[current_node as usize] && seen.insert((current_node, direction_idx)) {
+    path.insert((current_node, directions[direction_idx]));
+    current_node = network.step(current_node, directions[direction_idx]);
+    direction_idx = (direction_idx + 1) % directions.len();
   }
-  panic!("Cannot find cycle for node {}", current_node);
+
+  path
 }
 
-fn prob_a(directions: &Vec<Direction>, nodes: &HashMap<String, Node>) -> usize {
-  let mut steps: usize = 0;
-  let mut current_node: String = "AAA".to_owned();
-  let mut current_direction_idx = 0;
-  while current_node != "ZZZ" {
-    //println!("Current node {}", current_node);
-    match nodes.get(&current_node) {
-      Some(n) => current_node = if directions[current_direction_idx] == Direction::Left { n.left.clone() } else { n.right.clone() },
-      None => panic!("Could not find node {}", current_node)
+// Graphviz DOT with start nodes in green, end nodes in red and the edges of path in bold blue
+fn to_dot(network: &Network, start: &Selector, end: &Selector, path: &HashSet<(u32, Direction)>) -> String {
+  let mut dot = String::from("digraph network {\n  node [shape=circle];\n");
+  for id in 0..network.len() as u32 {
+    let name = network.name(id);
+    if start.matches(name) {
+      writeln!(dot, "  \"{}\" [style=filled, fillcolor=palegreen];", name).unwrap();
+    } else if end.matches(name) {
+      writeln!(dot, "  \"{}\" [style=filled, fillcolor=lightcoral];", name).unwrap();
     }
-    steps = steps + 1;
-    current_direction_idx = current_direction_idx + 1;
-    if current_direction_idx >= directions.len() {
-      current_direction_idx = 0;
+  }
+  for id in 0..network.len() as u32 {
+    for (direction, label) in [(Direction::Left, "L"), (Direction::Right, "R")] {
+      let style = if path.contains(&(id, direction)) { ", color=blue, penwidth=2" } else { "" };
+      writeln!(dot, "  \"{}\" -> \"{}\" [label=\"{}\"{}];", network.name(id), network.name(network.step(id, direction)), label, style).unwrap();
     }
   }
+  dot.push_str("}\n");
 
-  steps
+  dot
 }
 
-fn prob_b(directions: &Vec<Direction>, nodes: &HashMap<String, Node>) -> usize {
-  let start = Instant::now();
-  let mut steps = 0;
-  let mut current_nodes: Vec<String> = find_start_nodes(&nodes);
-  let mut distances: HashMap<String, (usize, String)> = nodes.keys().map(|k| (k.clone(), (usize::MAX, "".to_owned()))).collect();
-  find_distances_to_end(&mut distances, &nodes, &directions);
-  println!("Time elapsed finding distances to end: {:?}", start.elapsed());
-  
-  while !all_nodes_have_same_distances(&current_nodes, &distances) {
-    current_nodes = current_nodes.iter().map(|n| distances.get(n).unwrap().1.clone() ).collect();
-    steps = steps + directions.len();
-    println!("Steps: {}, Time: {:?}", steps, start.elapsed());
-  }
+struct Options {
+  positional: Vec<String>,
+  start: Option<Selector>,
+  end: Option<Selector>,
+  path: Option<String>,
+  limit: usize
+}
 
-  match distances.get(&current_nodes[0]) {
-    Some(d) => return steps + d.0,
-    None => panic!("Could not find distance {}", current_nodes[0])
+fn parse_options(args: &[String]) -> Result<Options, String> {
+  let mut options = Options { positional: Vec::new(), start: None, end: None, path: None, limit: 10_000_000 };
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--start" => options.start = Some(Selector::parse(iter.next().ok_or("--start needs a selector")?)?),
+      "--end" => options.end = Some(Selector::parse(iter.next().ok_or("--end needs a selector")?)?),
+      "--path" => options.path = Some(iter.next().ok_or("--path needs a start node")?.clone()),
+      "--limit" => {
+        let limit = iter.next().ok_or("--limit needs a number of steps")?;
+        options.limit = limit.parse::<usize>().map_err(|_| format!("Invalid step limit {}", limit))?;
+      },
+      _ => options.positional.push(arg.clone())
+    }
   }
-}
 
-fn prob_b_2(directions: &Vec<Direction>, nodes: &HashMap<String, Node>) -> usize {
-  let start = Instant::now();
-  let mut steps = 0;
-  let mut current_nodes: Vec<String> = find_start_nodes(&nodes);
-  current_nodes.par_iter()
-    .map(|n| calc_cycle(n, nodes, directions))
-    .reduce(|| 1, |a, b| lcm(a, b))
+  Ok(options)
 }
 
-fn main() {
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day8 <file> [a|b|b-sim|query|dot] [options]")?;
   let start = Instant::now();
-  let args: Vec<String> = std::env::args().collect();
-  let binding = fs::read_to_string(&args[1]).unwrap();
+  let binding = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
   let file: &str = binding.as_str();
 
-  let (directions, nodes) = parse_input(file);
+  let (directions, network) = parse_input(file);
   println!("Time elapsed parsing input is: {:?}", start.elapsed());
-  let result = prob_b_2(&directions, &nodes);
+  // Day8 <file> [a|b|b-sim] [--start selector] [--end selector] [--limit steps]
+  // Day8 <file> query <from selector> <to selector>
+  // Day8 <file> dot <output file> [--start selector] [--end selector] [--path node]
+  let options = parse_options(&args[2..])?;
+  let mode = options.positional.first().map(|s| s.as_str());
+  let (default_start, default_end) = match mode {
+    Some("a") => ("exact:AAA", "exact:ZZZ"),
+    _ => ("suffix:A", "suffix:Z")
+  };
+  let start_nodes = options.start.unwrap_or(Selector::parse(default_start)?);
+  let end_nodes = options.end.unwrap_or(Selector::parse(default_end)?);
+  match mode {
+    Some("a") => {
+      let results = prob_a(&directions, &network, &start_nodes, &end_nodes);
+      if results.is_empty() {
+        return Err("No start node matches".to_owned());
+      }
+      for (node, steps) in results {
+        match steps {
+          Some(steps) => println!("Result: {} ({})", steps, network.name(node)),
+          None => println!("No end node is reachable from {}", network.name(node))
+        }
+      }
+    },
+    Some("b") | None => match prob_b_2(&directions, &network, &start_nodes, &end_nodes) {
+      Some(result) => println!("Result: {}", result),
+      None => println!("The ghosts never reach end nodes at the same time")
+    },
+    Some("b-sim") => match prob_b(&directions, &network, &start_nodes, &end_nodes, options.limit) {
+      Some(result) => println!("Result: {}", result),
+      None => println!("The ghosts do not reach end nodes at the same time within {} steps", options.limit)
+    },
+    Some("query") => match (options.positional.get(1), options.positional.get(2)) {
+      (Some(from), Some(to)) => query(&directions, &network, &Selector::parse(from)?, &Selector::parse(to)?)?,
+      _ => return Err("query needs a from and a to selector".to_owned())
+    },
+    Some("dot") => {
+      let output = options.positional.get(1).ok_or("dot needs an output file")?;
+      let path = match &options.path {
+        Some(name) => {
+          let node = network.id(name).ok_or(format!("Could not find node {}", name))?;
+          find_path(node, &network, &directions, &end_nodes.mark_nodes(&network))
+        },
+        None => HashSet::new()
+      };
+      fs::write(output, to_dot(&network, &start_nodes, &end_nodes, &path))
+        .map_err(|e| format!("Could not write {}: {}", output, e))?;
+      println!("Wrote {} nodes to {}", network.len(), output);
+    },
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
+  }
 
-  println!("Result: {}", result);
   println!("Time elapsed {:?}", start.elapsed());
-}
\ No newline at end of file
+  Ok(())
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn generate_network(seed: &mut u64) -> String {
+    let mut next = |bound: u64| {
+      *seed ^= *seed << 13;
+      *seed ^= *seed >> 7;
+      *seed ^= *seed << 17;
+      *seed % bound
+    };
+    let directions: String = (0..next(5) + 1).map(|_| if next(2) == 0 { 'L' } else { 'R' }).collect();
+    let size = next(9) + 2;
+    let starts = next(3) + 1;
+    let names: Vec<String> = (0..size)
+      .map(|idx| {
+        let suffix = if idx < starts { 'A' } else if next(3) == 0 { 'Z' } else { 'X' };
+        format!("{:02}{}", idx, suffix)
+      })
+      .collect();
+    let nodes: Vec<String> = names.iter()
+      .map(|name| format!("{} = ({}, {})", name, names[next(size) as usize], names[next(size) as usize]))
+      .collect();
+
+    format!("{}\n\n{}", directions, nodes.join("\n"))
+  }
+
+  // With at most 10 nodes, 5 directions and 3 ghosts, any simultaneous arrival happens within
+  // 50 + 50^3 steps, so a simulation that runs out of steps means there is none.
+  #[test]
+  fn cycle_solver_matches_simulation() {
+    let mut seed: u64 = 0x2545F4914F6CDD1D;
+    let start = Selector::parse("suffix:A").unwrap();
+    let end = Selector::parse("suffix:Z").unwrap();
+    for _ in 0..1000 {
+      let file = generate_network(&mut seed);
+      let (directions, network) = parse_input(&file);
+      let simulated = prob_b(&directions, &network, &start, &end, 50 + 50 * 50 * 50).map(|steps| steps as u128);
+      let (solved, _) = find_simultaneous_arrival(&find_walks(&directions, &network, &start, &end));
+      assert_eq!(simulated, solved, "for\n{}", file);
+    }
+  }
+}
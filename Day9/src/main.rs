@@ -1,119 +1,272 @@
+use std::fmt::Display;
 use std::fs;
-use std::collections::VecDeque;
+use std::str::FromStr;
+use num::{BigInt, BigRational, CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromPrimitive, Integer, One, Signed, Zero};
+
+// Any integer type the histories can be evaluated in. Every operation is checked,
+// so a reading type that is too narrow reports an overflow instead of wrapping.
+trait Reading: Clone + PartialEq + Display + FromStr + Zero + One
+  + Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + FromPrimitive + Into<BigInt> {}
+
+impl<T> Reading for T where T: Clone + PartialEq + Display + FromStr + Zero + One
+  + Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + FromPrimitive + Into<BigInt> {}
 
 #[derive(Debug)]
-struct History {
-  readings: Vec<VecDeque<i32>>
+struct History<T> {
+  readings: Vec<Vec<T>>,
+  // False when the differences run out of values before a row of zeros shows up,
+  // in which case the extrapolation is only a guess.
+  reaches_zero: bool
 }
 
-impl History {
-  pub fn new(readings: Vec<i32>) -> Self {
-    let mut own_readings: Vec<VecDeque<i32>> = Vec::new();
+impl<T: Reading> History<T> {
+  pub fn new(readings: Vec<T>) -> Result<Self, String> {
+    let mut own_readings: Vec<Vec<T>> = Vec::new();
     let mut idx = 0;
-    own_readings.push(VecDeque::from(readings));
-    while !all_readings_are_zero(&(own_readings[idx])) {
-      own_readings.push(calc_differences(&(own_readings[idx])));
-      idx = idx + 1;
-    }
-    Self {
-      readings: own_readings
+    own_readings.push(readings);
+    let mut reaches_zero = true;
+    while own_readings[idx].is_empty() || !all_readings_are_zero(&(own_readings[idx])) {
+      if own_readings[idx].len() <= 1 {
+        reaches_zero = false;
+        break;
+      }
+      own_readings.push(calc_differences(&(own_readings[idx]))?);
+      idx += 1;
     }
+    Ok(Self {
+      readings: own_readings,
+      reaches_zero
+    })
   }
 
-  fn get_next_value(&mut self) -> i32 {
-    if self.readings.len() == 0 {
-      panic!("Cannot get next value! There are no readings in history!");
+  // Newton's forward difference formula: f(x) = sum over j of C(x, j) * (j-th difference at 0),
+  // which holds for any integer x, including positions before the first reading. Only rows up
+  // to the degree contribute, and C(x, j + 1) = C(x, j) * (x - j) / (j + 1) is computed by
+  // cancelling the gcd first so the product never grows past the result.
+  fn value_at(&self, index: i64) -> Result<T, String> {
+    if self.readings[0].is_empty() {
+      return Err("Cannot get a value! There are no readings in history!".to_owned());
     }
-    let mut idx = self.readings.len() - 1;
-    self.readings[idx].push_back(0);
-    while idx > 0 {
-      let last_upper_reading = self.readings[idx - 1].back().unwrap().clone();
-      let last_current_reading = self.readings[idx].back().unwrap().clone();
-      self.readings[idx - 1].push_back(last_upper_reading + last_current_reading);
-      idx = idx - 1;
+    let overflow = || format!("Overflow evaluating position {}", index);
+    let degree = self.degree();
+    let mut value = T::zero();
+    let mut binomial = T::one();
+    for (j, row) in self.readings.iter().enumerate().take(degree + 1) {
+      let Some(first) = row.first() else { break };
+      value = binomial.checked_mul(first).and_then(|term| value.checked_add(&term)).ok_or_else(overflow)?;
+      if j == degree {
+        break;
+      }
+      let factor = index.checked_sub(j as i64).and_then(T::from_i64).ok_or_else(overflow)?;
+      let divisor = T::from_usize(j + 1).ok_or_else(overflow)?;
+      // C(x, j) * (x - j) is divisible by j + 1, and binomial / gcd shares no factor with
+      // divisor / gcd, so the whole of divisor / gcd divides x - j
+      let gcd = binomial.gcd(&divisor);
+      let (binomial_part, divisor_part) = (binomial.div_floor(&gcd), divisor.div_floor(&gcd));
+      binomial = binomial_part.checked_mul(&factor.div_floor(&divisor_part)).ok_or_else(overflow)?;
     }
 
-    *self.readings[0].back().unwrap()
+    Ok(value)
   }
 
-  fn get_previous_value(&mut self) -> i32 {
-    if self.readings.len() == 0 {
-      panic!("Cannot get next value! There are no readings in history!");
+  // Degree of the lowest polynomial through the readings: the row above the zero row is constant.
+  // A history without a zero row is only fitted by a polynomial of degree len - 1.
+  fn degree(&self) -> usize {
+    if self.reaches_zero {
+      self.readings.len().saturating_sub(2)
+    } else {
+      self.readings[0].len().saturating_sub(1)
     }
-    let mut idx = self.readings.len() - 1;
-    self.readings[idx].push_front(0);
-    while idx > 0 {
-      let first_upper_reading = self.readings[idx - 1].front().unwrap().clone();
-      let first_current_reading = self.readings[idx].front().unwrap().clone();
-      self.readings[idx - 1].push_front(first_upper_reading - first_current_reading);
-      idx = idx - 1;
+  }
+
+  // Expands the Newton form into x^0, x^1, ... coefficients, with x the 0-based position of a reading.
+  // The falling factorial x(x-1)...(x-j+1) is built up one factor at a time and divided by j!.
+  fn coefficients(&self) -> Vec<BigRational> {
+    let degree = self.degree();
+    let mut coefficients = vec![BigRational::zero(); degree + 1];
+    let mut falling: Vec<BigInt> = vec![BigInt::one()];
+    let mut factorial = BigInt::one();
+    for j in 0..=degree {
+      let Some(first) = self.readings.get(j).and_then(|row| row.first()) else { break };
+      let difference = BigRational::new(first.clone().into(), factorial.clone());
+      for (power, factor) in falling.iter().enumerate() {
+        coefficients[power] += &difference * BigRational::from_integer(factor.clone());
+      }
+      // falling *= (x - j)
+      let mut next = vec![BigInt::zero(); falling.len() + 1];
+      for (power, factor) in falling.iter().enumerate() {
+        next[power + 1] += factor;
+        next[power] -= factor * BigInt::from(j);
+      }
+      falling = next;
+      factorial *= BigInt::from(j + 1);
     }
 
-    *self.readings[0].front().unwrap()
+    coefficients
   }
-}
 
-fn calc_differences(readings: &VecDeque<i32>) -> VecDeque<i32> {
-  let mut idx = 1;
-  let mut differences: VecDeque<i32> = VecDeque::new();
-  while idx < readings.len() {
-    differences.push_back(readings[idx] - readings[idx - 1]);
-    idx = idx + 1;
+  fn extrapolate_forward(&self, steps: usize) -> Result<Vec<T>, String> {
+    let len = self.readings[0].len() as i64;
+    (0..steps as i64).map(|k| self.value_at(len + k)).collect()
+  }
+
+  fn extrapolate_backward(&self, steps: usize) -> Result<Vec<T>, String> {
+    (1..=steps as i64).map(|k| self.value_at(-k)).collect()
   }
-  differences
 }
 
-fn all_readings_are_zero(readings: &VecDeque<i32>) -> bool {
+fn calc_differences<T: Reading>(readings: &[T]) -> Result<Vec<T>, String> {
+  readings.windows(2)
+    .map(|pair| pair[1].checked_sub(&pair[0]).ok_or(format!("Overflow subtracting {} from {}", pair[0], pair[1])))
+    .collect()
+}
+
+fn all_readings_are_zero<T: Reading>(readings: &[T]) -> bool {
   for reading in readings {
-    if *reading != 0 {
+    if !reading.is_zero() {
       return false;
     }
   }
   true
 }
 
-fn parse_input(lines: Vec<&str>) -> Vec<Vec<i32>> {
+fn parse_input<T: Reading>(lines: Vec<&str>) -> Result<Vec<History<T>>, String> {
   let mut histories = Vec::new();
-  for line in lines {
-    let readings: Vec<i32> = line.split(" ").map(|r| r.parse::<i32>().unwrap() ).collect();
-    histories.push(readings);
+  for (idx, line) in lines.iter().enumerate() {
+    let readings = line.split_whitespace()
+      .map(|r| r.parse::<T>().map_err(|_| format!("Line {}: Invalid reading {}", idx + 1, r)))
+      .collect::<Result<Vec<T>, String>>()?;
+    if readings.is_empty() {
+      return Err(format!("Line {}: History has no readings", idx + 1));
+    }
+    let history = History::new(readings).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+    if !history.reaches_zero {
+      eprintln!("Warning: Line {}: History never reaches an all-zero difference row", idx + 1);
+    }
+    histories.push(history);
   }
 
-  histories
+  Ok(histories)
 }
 
-fn prob_a(hist_readings: Vec<Vec<i32>>) -> i32 {
-  let mut sum: i32 = 0;
-  let mut histories: Vec<History> = Vec::new();
-  for readings in hist_readings {
-    histories.push(History::new(readings));
-  }
-  for history in &mut histories {
-    sum = sum + history.get_next_value();
+fn format_polynomial(coefficients: &[BigRational]) -> String {
+  let mut terms = String::new();
+  for (power, coefficient) in coefficients.iter().enumerate().rev() {
+    if coefficient.is_zero() {
+      continue;
+    }
+    let sign = if coefficient.is_negative() { "-" } else { "+" };
+    if terms.is_empty() {
+      terms.push_str(if coefficient.is_negative() { "-" } else { "" });
+    } else {
+      terms.push_str(&format!(" {} ", sign));
+    }
+    let magnitude = coefficient.abs();
+    if power == 0 || magnitude.is_integer() && !magnitude.is_one() {
+      terms.push_str(&magnitude.to_string());
+    } else if !magnitude.is_integer() {
+      terms.push_str(&format!("({})", magnitude));
+    }
+    match power {
+      0 => (),
+      1 => terms.push('x'),
+      _ => terms.push_str(&format!("x^{}", power))
+    }
   }
-  sum
+
+  if terms.is_empty() { "0".to_owned() } else { terms }
 }
 
-fn prob_b(hist_readings: Vec<Vec<i32>>) -> i32 {
-  let mut sum: i32 = 0;
-  let mut histories: Vec<History> = Vec::new();
-  for readings in hist_readings {
-    histories.push(History::new(readings));
+fn print_polynomials<T: Reading>(histories: &[History<T>]) {
+  for (idx, history) in histories.iter().enumerate() {
+    let degree = history.degree();
+    let flag = if !history.reaches_zero {
+      "  (no pattern: degree is length - 1)"
+    } else {
+      ""
+    };
+    println!("Line {}: degree {}: p(x) = {}{}", idx + 1, degree, format_polynomial(&history.coefficients()), flag);
   }
-  for history in &mut histories {
-    sum = sum + history.get_previous_value();
+}
+
+// Adds up one value per history, tagging any failure with the history's line number.
+fn checked_sum<T: Reading>(histories: &[History<T>], value: impl Fn(&History<T>) -> Result<T, String>) -> Result<T, String> {
+  let mut sum = T::zero();
+  for (idx, history) in histories.iter().enumerate() {
+    let value = value(history).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+    sum = sum.checked_add(&value).ok_or(format!("Line {}: Overflow adding to the sum", idx + 1))?;
   }
-  sum
+  Ok(sum)
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  let file: String = fs::read_to_string(&args[1]).unwrap();
+fn prob_a<T: Reading>(histories: &[History<T>], steps: usize) -> Result<T, String> {
+  checked_sum(histories, |history| Ok(history.extrapolate_forward(steps)?.pop().unwrap_or(T::zero())))
+}
+
+fn prob_b<T: Reading>(histories: &[History<T>], steps: usize) -> Result<T, String> {
+  checked_sum(histories, |history| Ok(history.extrapolate_backward(steps)?.pop().unwrap_or(T::zero())))
+}
+
+fn sum_at<T: Reading>(histories: &[History<T>], index: i64) -> Result<T, String> {
+  checked_sum(histories, |history| history.value_at(index))
+}
+
+fn solve<T: Reading>(lines: Vec<&str>, mode: Option<&str>, args: &[String], steps: usize) -> Result<(), String> {
+  let histories = parse_input::<T>(lines)?;
+  let result = match mode {
+    Some("a") => prob_a(&histories, steps)?,
+    Some("b") | None => prob_b(&histories, steps)?,
+    Some("at") => {
+      let index = args.get(1).ok_or("at needs an index")?;
+      sum_at(&histories, index.parse::<i64>().map_err(|_| format!("Invalid index {}", index))?)?
+    },
+    Some("poly") => {
+      print_polynomials(&histories);
+      return Ok(());
+    },
+    Some(mode) => return Err(format!("Unknown mode {}", mode))
+  };
+
+  println!("Result: {}", result);
+  Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let path = args.get(1).ok_or("Usage: Day9 <file> [a|b|at <index>|poly] [--steps k] [--int i64|i128|big]")?;
+  let file: String = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
   let lines: Vec<&str> = file.lines()
     .collect();
 
-  let histories = parse_input(lines);
-  let result = prob_b(histories);
+  // Day9 <file> [a|b] [--steps k] [--int i64|i128|big]
+  // Day9 <file> at <index> [--int i64|i128|big]
+  // Day9 <file> poly [--int i64|i128|big]
+  let mut positional: Vec<String> = Vec::new();
+  let mut steps = 1;
+  let mut int = "i64".to_owned();
+  let mut iter = args[2..].iter();
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--steps" => {
+        let value = iter.next().ok_or("--steps needs a number")?;
+        steps = value.parse::<usize>().map_err(|_| format!("Invalid step count {}", value))?;
+      },
+      "--int" => int = iter.next().ok_or("--int needs i64, i128 or big")?.clone(),
+      _ => positional.push(arg.clone())
+    }
+  }
+  let mode = positional.first().map(|s| s.as_str());
+  match int.as_str() {
+    "i64" => solve::<i64>(lines, mode, &positional, steps),
+    "i128" => solve::<i128>(lines, mode, &positional, steps),
+    "big" => solve::<BigInt>(lines, mode, &positional, steps),
+    _ => Err(format!("Unknown integer type {}, expected i64, i128 or big", int))
+  }
+}
 
-  println!("Result: {}", result);
-}
\ No newline at end of file
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Err(e) = run(&args) {
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+  }
+}